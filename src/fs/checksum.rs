@@ -0,0 +1,88 @@
+use std::io::Read;
+
+/// The size of the buffer used when computing a checksum from a reader.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The algorithm used to compute the checksum of each node in a filestore.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// The standard CRC-32 (IEEE) checksum, which is used by the retail client.
+    #[default]
+    Crc32,
+    /// The wrapping sum of every byte, which is used by some older server builds.
+    Additive,
+}
+
+/// Incrementally computes a checksum over data which is provided in multiple parts.
+pub enum ChecksumHasher {
+    Crc32(crc32fast::Hasher),
+    Additive(u32),
+}
+
+impl ChecksumAlgorithm {
+    /// Computes the checksum of some data.
+    ///
+    /// # Arguments
+    /// * `data`    - The data to compute the checksum of.
+    pub fn checksum(&self, data: &[u8]) -> u32 {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Computes the checksum of the next `length` bytes of a reader. The data is hashed in fixed
+    /// size chunks, so memory use doesn't grow with the length of the data.
+    ///
+    /// # Arguments
+    /// * `src`     - The reader to consume the data from.
+    /// * `length`  - The number of bytes to hash.
+    pub fn checksum_reader<R: Read>(
+        &self,
+        src: &mut R,
+        length: usize,
+    ) -> Result<u32, std::io::Error> {
+        let mut hasher = self.hasher();
+        let mut buf = vec![0; READ_CHUNK_SIZE.min(length)];
+        let mut remaining = length;
+        while remaining > 0 {
+            let chunk = &mut buf[..READ_CHUNK_SIZE.min(remaining)];
+            src.read_exact(chunk)?;
+            hasher.update(chunk);
+            remaining -= chunk.len();
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Creates a hasher which incrementally computes a checksum with this algorithm.
+    pub fn hasher(&self) -> ChecksumHasher {
+        match *self {
+            Self::Crc32 => ChecksumHasher::Crc32(crc32fast::Hasher::new()),
+            Self::Additive => ChecksumHasher::Additive(0),
+        }
+    }
+}
+
+impl ChecksumHasher {
+    /// Adds some data to the checksum.
+    ///
+    /// # Arguments
+    /// * `data`    - The data.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Crc32(hasher) => hasher.update(data),
+            Self::Additive(sum) => {
+                *sum = data
+                    .iter()
+                    .fold(*sum, |sum, byte| sum.wrapping_add(*byte as u32))
+            }
+        }
+    }
+
+    /// Gets the checksum of all the data that has been provided.
+    pub fn finalize(self) -> u32 {
+        match self {
+            Self::Crc32(hasher) => hasher.finalize(),
+            Self::Additive(sum) => sum,
+        }
+    }
+}
@@ -11,14 +11,35 @@ const SAH_MAGIC_VALUE: &str = "SAH";
 /// The header file format version. This is always set to zero.
 const HEADER_FORMAT_VERSION: u32 = 0;
 
+/// The length of the reserved region which follows the file count. Some clients store a build
+/// timestamp or flags here, so it's preserved rather than zeroed.
+const RESERVED_LENGTH: usize = 40;
+
+/// The maximum length of a directory or file name, including the null terminator. Anything longer
+/// than this is treated as a corrupt header, rather than attempting to allocate it.
+const MAX_NAME_LENGTH: usize = 256;
+
 /// A listing of every [VirtualDirectory] and every [Inode] contained within the virtual
 /// filesystem. This is serialized to a `.SAH` file and used by the game client to look up files
 /// by their path, relative to the root directory.
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct Header {
     root: VirtualDirectory,
+    declared_file_count: Option<usize>,
+    #[serde(with = "reserved_bytes")]
+    reserved: [u8; RESERVED_LENGTH],
+    trailer: u64,
+}
+
+/// Builds a [Header] from a list of nodes, which are assumed to be laid out sequentially in the
+/// data file, in the order they were added.
+#[derive(Default)]
+pub struct HeaderBuilder {
+    nodes: Vec<(String, usize, u32)>,
 }
 
 /// A directory within the virtual filesystem, which may contain file nodes and other directories.
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct VirtualDirectory {
     pub name: String,
     pub subdirectories: Vec<VirtualDirectory>,
@@ -26,6 +47,7 @@ pub struct VirtualDirectory {
 }
 
 /// Represents a file node in the filesystem's corresponding data file.
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct Inode {
     pub name: String,
     pub offset: usize,
@@ -33,6 +55,14 @@ pub struct Inode {
     pub checksum: u32,
 }
 
+/// A copy of the metadata of an [Inode], which can be handed out without exposing the node itself.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InodeInfo {
+    pub offset: usize,
+    pub length: usize,
+    pub checksum: u32,
+}
+
 #[derive(Error, Debug)]
 pub enum HeaderDeserializeError {
     #[error("read error")]
@@ -60,6 +90,27 @@ impl Header {
         Self::deserialize(&mut src)
     }
 
+    /// Gets the number of files that the header declared it contained when it was deserialized.
+    /// If this disagrees with the actual number of nodes, the header is likely corrupt. This
+    /// will return `None` if the header was not deserialized.
+    pub fn declared_file_count(&self) -> Option<usize> {
+        self.declared_file_count
+    }
+
+    /// Gets the reserved region of the header, exactly as it was read. This is zeroed for
+    /// headers which were not deserialized.
+    pub fn reserved(&self) -> &[u8; RESERVED_LENGTH] {
+        &self.reserved
+    }
+
+    /// Sets the reserved region of the header, which is written back verbatim.
+    ///
+    /// # Arguments
+    /// * `reserved`    - The reserved bytes.
+    pub fn set_reserved(&mut self, reserved: [u8; RESERVED_LENGTH]) {
+        self.reserved = reserved;
+    }
+
     /// Recursively gets the path to all nodes in the header. This is useful for getting
     /// a listing of everything contained within the filesystem.
     pub fn get_all_node_paths(&self) -> Vec<String> {
@@ -75,8 +126,81 @@ impl Header {
         paths
     }
 
+    /// Recursively gets the path to every directory in the header, excluding the root. Only the
+    /// directories are visited, so this is much cheaper than [Self::get_all_node_paths] when
+    /// only the folder structure is needed.
+    pub fn all_directory_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for subdir in &self.root.subdirectories {
+            subdir.directory_paths(&subdir.name, &mut paths);
+        }
+
+        paths
+    }
+
+    /// Recursively gets the path and metadata of every node in the header, in a single walk of the
+    /// directory tree.
+    pub fn get_all_entries(&self) -> Vec<(String, InodeInfo)> {
+        let mut entries = Vec::new();
+        for inode in &self.root.nodes {
+            entries.push((inode.name.clone(), InodeInfo::from(inode)));
+        }
+
+        for subdir in &self.root.subdirectories {
+            entries.extend(subdir.node_entries());
+        }
+
+        entries
+    }
+
+    /// Finds every pair of nodes whose data regions intersect, which can only happen if the header
+    /// is corrupt. Nodes which point at exactly the same region are not reported, as deduplicated
+    /// nodes share their data intentionally.
+    pub fn check_overlaps(&self) -> Vec<(String, String)> {
+        let mut entries = self.get_all_entries();
+        entries.retain(|(_, info)| info.length > 0);
+        entries.sort_by_key(|(_, info)| info.offset);
+
+        let mut overlaps = Vec::new();
+        for (index, (path, info)) in entries.iter().enumerate() {
+            // A corrupt header may describe a region which runs past the end of the address
+            // space, so it's treated as extending to the end instead of overflowing.
+            let end = info.offset.saturating_add(info.length);
+            for (other_path, other) in entries[index + 1..]
+                .iter()
+                .take_while(|(_, other)| other.offset < end)
+            {
+                if other.offset != info.offset || other.length != info.length {
+                    overlaps.push((path.clone(), other_path.clone()));
+                }
+            }
+        }
+
+        overlaps
+    }
+
+    /// Walks the directory tree depth-first, invoking a visitor with the full path and metadata
+    /// of every node. The nodes of a directory are visited before its subdirectories, in the order
+    /// they appear in the header.
+    ///
+    /// # Arguments
+    /// * `visitor` - The function to call for each node.
+    pub fn walk<F>(&self, visitor: &mut F)
+    where
+        F: FnMut(&str, &InodeInfo),
+    {
+        for inode in &self.root.nodes {
+            visitor(&inode.name, &InodeInfo::from(inode));
+        }
+
+        for subdir in &self.root.subdirectories {
+            subdir.walk_nodes(&subdir.name, visitor);
+        }
+    }
+
     /// Gets an inode contained within the filesystem. This will return `None` if either a directory
-    /// or the file does not exist. The path is case-insensitive.
+    /// or the file does not exist. The path is case-insensitive, and may use either `/` or `\` as
+    /// a separator.
     ///
     /// # Arguments
     /// * `virtual_path`    - The path to the node.
@@ -84,8 +208,7 @@ impl Header {
     where
         T: AsRef<str>,
     {
-        let virtual_path = virtual_path.as_ref();
-        let mut parts = virtual_path.split('/').collect::<VecDeque<_>>();
+        let mut parts = path_components(virtual_path.as_ref());
 
         let mut directory = &self.root;
         while parts.len() > 1 {
@@ -115,7 +238,8 @@ impl Header {
     }
 
     /// Gets an inode contained within the filesystem. This will return `None` if either a directory
-    /// or the file does not exist. The path is case-insensitive.
+    /// or the file does not exist. The path is case-insensitive, and may use either `/` or `\` as
+    /// a separator.
     ///
     /// # Arguments
     /// * `virtual_path`    - The path to the node.
@@ -123,8 +247,7 @@ impl Header {
     where
         T: AsRef<str>,
     {
-        let virtual_path = virtual_path.as_ref();
-        let mut parts = virtual_path.split('/').collect::<VecDeque<_>>();
+        let mut parts = path_components(virtual_path.as_ref());
 
         let mut directory = &mut self.root;
         while parts.len() > 1 {
@@ -152,8 +275,79 @@ impl Header {
         None
     }
 
+    /// Removes an inode from the filesystem, and returns it. This will return `None` if either a
+    /// directory or the file does not exist. The path is case-insensitive, and may use either `/`
+    /// or `\` as a separator. Directories which are left empty are kept.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the node.
+    pub fn remove_inode<T>(&mut self, virtual_path: &T) -> Option<Inode>
+    where
+        T: AsRef<str>,
+    {
+        let mut parts = path_components(virtual_path.as_ref());
+        let name = parts.pop_back()?;
+
+        let mut directory = &mut self.root;
+        for subdir in parts {
+            directory = directory.get_subdirectory(subdir)?;
+        }
+
+        let index = directory
+            .nodes
+            .iter()
+            .position(|node| node.name.eq_ignore_ascii_case(name))?;
+        Some(directory.nodes.remove(index))
+    }
+
+    /// Finds the first inode in the filesystem which matches a predicate.
+    ///
+    /// # Arguments
+    /// * `predicate`   - The predicate to match against.
+    pub fn find_inode<F>(&self, predicate: F) -> Option<&Inode>
+    where
+        F: Fn(&Inode) -> bool,
+    {
+        self.root.find_node(&predicate)
+    }
+
+    /// Counts the inodes in the filesystem which match a predicate.
+    ///
+    /// # Arguments
+    /// * `predicate`   - The predicate to match against.
+    pub fn count_inodes<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&Inode) -> bool,
+    {
+        self.root.count_nodes(&predicate)
+    }
+
+    /// Counts every node in the filesystem, without allocating their paths.
+    pub fn node_count(&self) -> usize {
+        self.count_inodes(|_| true)
+    }
+
+    /// Counts every node in a directory, including the nodes in all of its subdirectories. This
+    /// will return `None` if the directory does not exist. The path is case-insensitive, may use
+    /// either `/` or `\` as a separator, and an empty path refers to the root directory.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the directory.
+    pub fn dir_node_count(&self, virtual_path: impl AsRef<str>) -> Option<usize> {
+        let mut directory = &self.root;
+        for name in path_components(virtual_path.as_ref()) {
+            directory = directory
+                .subdirectories
+                .iter()
+                .find(|sub| sub.name.eq_ignore_ascii_case(name))?;
+        }
+
+        Some(directory.count_nodes(&|_: &Inode| true))
+    }
+
     /// Places an [Inode] into an appropriate position in this filesystem. If the subdirectories
-    /// required to reach this node do not yet exist, they will be allocated.
+    /// required to reach this node do not yet exist, they will be allocated. The path may use
+    /// either `/` or `\` as a separator.
     ///
     /// # Arguments
     /// * `virtual_path`    - The path to place the node.
@@ -162,8 +356,7 @@ impl Header {
     where
         T: AsRef<str>,
     {
-        let virtual_path = virtual_path.as_ref();
-        let mut parts = virtual_path.split('/').collect::<VecDeque<_>>();
+        let mut parts = path_components(virtual_path.as_ref());
 
         let mut directory = &mut self.root;
         while parts.len() > 1 {
@@ -173,16 +366,53 @@ impl Header {
                     .get_subdirectory(name)
                     .expect("failed to get known existing subdirectory");
             } else {
-                directory = directory.create_and_get_subdirectory(name);
+                directory = directory.create_and_get_subdirectory(name)?;
             }
         }
 
-        assert_eq!(parts.len(), 1);
         directory.nodes.push(node);
         Ok(())
     }
 }
 
+/// Splits a virtual path into its directory and file names. Both `/` and `\` are accepted as
+/// separators, and leading, trailing or repeated separators are ignored, so `/item/item.sdata` and
+/// `item\item.sdata` both refer to the same node as `item/item.sdata`.
+///
+/// # Arguments
+/// * `virtual_path`    - The path to split.
+pub(crate) fn path_components(virtual_path: &str) -> VecDeque<&str> {
+    virtual_path
+        .split(['/', '\\'])
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Serde only implements its traits for arrays of up to 32 elements, so the reserved region is
+/// represented as a sequence of bytes instead.
+mod reserved_bytes {
+    use super::RESERVED_LENGTH;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(reserved: &[u8; RESERVED_LENGTH], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(reserved)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<[u8; RESERVED_LENGTH], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| D::Error::invalid_length(bytes.len(), &"40 reserved bytes"))
+    }
+}
+
 impl Default for Header {
     fn default() -> Self {
         let root = VirtualDirectory {
@@ -191,7 +421,84 @@ impl Default for Header {
             nodes: Vec::new(),
         };
 
-        Self { root }
+        Self {
+            root,
+            declared_file_count: None,
+            reserved: [0; RESERVED_LENGTH],
+            trailer: 0,
+        }
+    }
+}
+
+impl HeaderBuilder {
+    /// Creates a builder with no nodes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node to the header. It will be placed directly after the previously added node in
+    /// the data file.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the node.
+    /// * `length`          - The length of the node's data.
+    /// * `checksum`        - The checksum of the node's data.
+    pub fn node<T>(mut self, virtual_path: T, length: usize, checksum: u32) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.nodes
+            .push((virtual_path.as_ref().to_owned(), length, checksum));
+        self
+    }
+
+    /// Adds multiple nodes to the header, in order. Each is placed directly after the previously
+    /// added node in the data file.
+    ///
+    /// # Arguments
+    /// * `nodes`   - The path, length and checksum of each node.
+    pub fn nodes<I, T>(self, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = (T, usize, u32)>,
+        T: AsRef<str>,
+    {
+        nodes
+            .into_iter()
+            .fold(self, |builder, (virtual_path, length, checksum)| {
+                builder.node(virtual_path, length, checksum)
+            })
+    }
+
+    /// Builds the header, assigning each node a sequential offset starting from zero.
+    pub fn build(self) -> Result<Header, std::io::Error> {
+        let mut header = Header::default();
+        let mut offset = 0;
+        for (virtual_path, length, checksum) in self.nodes {
+            let name = path_components(&virtual_path)
+                .pop_back()
+                .unwrap_or_default();
+            let inode = Inode {
+                name: name.to_owned(),
+                offset,
+                length,
+                checksum,
+            };
+
+            header.emplace_node(&virtual_path, inode)?;
+            offset += length;
+        }
+
+        Ok(header)
+    }
+}
+
+impl From<&Inode> for InodeInfo {
+    fn from(inode: &Inode) -> Self {
+        Self {
+            offset: inode.offset,
+            length: inode.length,
+            checksum: inode.checksum,
+        }
     }
 }
 
@@ -219,27 +526,78 @@ impl VirtualDirectory {
     }
 
     /// Creates an empty subdirectory with a given name, and returns it.
+    /// This fails if a subdirectory with the same name, ignoring case, already exists.
     ///
     /// # Arguments
     /// * `name`    - The name of the subdirectory.
-    fn create_and_get_subdirectory(&mut self, name: &str) -> &mut VirtualDirectory {
-        self.create_subdirectory(name);
-        self.get_subdirectory(name)
-            .expect("failed to get previously created subdirectory")
+    fn create_and_get_subdirectory(
+        &mut self,
+        name: &str,
+    ) -> Result<&mut VirtualDirectory, std::io::Error> {
+        self.create_subdirectory(name)?;
+        self.get_subdirectory(name).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("failed to get created subdirectory {name}"),
+            )
+        })
     }
 
     /// Creates an empty subdirectory with a given name.
+    /// This fails if a subdirectory with the same name, ignoring case, already exists.
     ///
     /// # Arguments
     /// * `name`    - The name of the subdirectory.
-    fn create_subdirectory(&mut self, name: &str) {
-        assert!(!self.subdirectory_exists(name));
+    fn create_subdirectory(&mut self, name: &str) -> Result<(), std::io::Error> {
+        if self.subdirectory_exists(name) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("subdirectory {name} already exists in {}", self.name),
+            ));
+        }
+
         let subdirectory = VirtualDirectory {
             name: name.to_string(),
             subdirectories: Vec::new(),
             nodes: Vec::new(),
         };
         self.subdirectories.push(subdirectory);
+        Ok(())
+    }
+
+    /// Recursively finds the first node in this directory, or any of it's subdirectories, which
+    /// matches a predicate.
+    ///
+    /// # Arguments
+    /// * `predicate`   - The predicate to match against.
+    fn find_node<F>(&self, predicate: &F) -> Option<&Inode>
+    where
+        F: Fn(&Inode) -> bool,
+    {
+        self.nodes.iter().find(|node| predicate(node)).or_else(|| {
+            self.subdirectories
+                .iter()
+                .find_map(|subdir| subdir.find_node(predicate))
+        })
+    }
+
+    /// Recursively counts the nodes in this directory, and all of it's subdirectories, which
+    /// match a predicate.
+    ///
+    /// # Arguments
+    /// * `predicate`   - The predicate to match against.
+    fn count_nodes<F>(&self, predicate: &F) -> usize
+    where
+        F: Fn(&Inode) -> bool,
+    {
+        let nodes = self.nodes.iter().filter(|node| predicate(node)).count();
+        let child_nodes = self
+            .subdirectories
+            .iter()
+            .map(|subdir| subdir.count_nodes(predicate))
+            .sum::<usize>();
+
+        nodes + child_nodes
     }
 
     /// Recursively gets the path to every node in this directory, and all of it's subdirectories.
@@ -258,6 +616,54 @@ impl VirtualDirectory {
 
         paths
     }
+
+    /// Recursively collects the path to this directory, and all of it's subdirectories.
+    ///
+    /// # Arguments
+    /// * `path`    - The full path to this directory.
+    /// * `paths`   - The paths collected so far.
+    fn directory_paths(&self, path: &str, paths: &mut Vec<String>) {
+        paths.push(path.to_owned());
+        for subdir in &self.subdirectories {
+            subdir.directory_paths(&format!("{}/{}", path, subdir.name), paths);
+        }
+    }
+
+    /// Recursively visits every node in this directory, and then all of it's subdirectories.
+    ///
+    /// # Arguments
+    /// * `path`    - The full path to this directory.
+    /// * `visitor` - The function to call for each node.
+    fn walk_nodes<F>(&self, path: &str, visitor: &mut F)
+    where
+        F: FnMut(&str, &InodeInfo),
+    {
+        for inode in &self.nodes {
+            visitor(&format!("{}/{}", path, inode.name), &InodeInfo::from(inode));
+        }
+
+        for subdir in &self.subdirectories {
+            subdir.walk_nodes(&format!("{}/{}", path, subdir.name), visitor);
+        }
+    }
+
+    /// Recursively gets the path and metadata of every node in this directory, and all of it's
+    /// subdirectories.
+    fn node_entries(&self) -> Vec<(String, InodeInfo)> {
+        let mut entries = Vec::with_capacity(self.nodes.len());
+        for inode in &self.nodes {
+            let path = format!("{}/{}", &self.name, inode.name);
+            entries.push((path, InodeInfo::from(inode)));
+        }
+
+        for subdir in &self.subdirectories {
+            for (path, info) in subdir.node_entries() {
+                entries.push((format!("{}/{}", &self.name, path), info));
+            }
+        }
+
+        entries
+    }
 }
 
 impl Deserialize for Header {
@@ -277,11 +683,32 @@ impl Deserialize for Header {
         }
 
         let _header_version = src.read_u32::<byteorder::LittleEndian>()?;
-        let _total_files = src.read_u32::<byteorder::LittleEndian>()?;
-        src.skip(40)?;
+        let total_files = src.read_u32::<byteorder::LittleEndian>()? as usize;
+        let mut reserved = [0; RESERVED_LENGTH];
+        src.read_exact(&mut reserved)?;
 
         let root = VirtualDirectory::deserialize(src)?;
-        Ok(Header { root })
+
+        // The header ends with eight bytes of unknown purpose, which are always zero in the
+        // files we've seen. Some tools don't write them, so treat a missing trailer as zero.
+        let trailer = match src.read_u64::<byteorder::LittleEndian>() {
+            Ok(trailer) => trailer,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+            Err(err) => return Err(err.into()),
+        };
+
+        let header = Header {
+            root,
+            declared_file_count: Some(total_files),
+            reserved,
+            trailer,
+        };
+
+        let actual_files = header.get_all_node_paths().len();
+        if actual_files != total_files {
+            tracing::warn!(total_files, actual_files, "mismatched header file count");
+        }
+        Ok(header)
     }
 }
 
@@ -293,7 +720,7 @@ impl Deserialize for VirtualDirectory {
         T: Read + ReadBytesExt,
         Self: Sized,
     {
-        let name = src.read_length_prefixed_string()?;
+        let name = src.read_length_prefixed_string_capped(MAX_NAME_LENGTH)?;
         let node_qty = src.read_u32::<byteorder::LittleEndian>()? as usize;
         let nodes = (0..node_qty)
             .map(|_| Inode::deserialize(src))
@@ -320,7 +747,7 @@ impl Deserialize for Inode {
         T: Read + ReadBytesExt,
         Self: Sized,
     {
-        let name = src.read_length_prefixed_string()?;
+        let name = src.read_length_prefixed_string_capped(MAX_NAME_LENGTH)?;
         let offset = src.read_u64::<byteorder::LittleEndian>()? as usize;
         let length = src.read_u32::<byteorder::LittleEndian>()? as usize;
         let checksum = src.read_u32::<byteorder::LittleEndian>()?;
@@ -346,10 +773,9 @@ impl Serialize for Header {
         dst.write_string(SAH_MAGIC_VALUE, 3)?;
         dst.write_u32::<byteorder::LittleEndian>(HEADER_FORMAT_VERSION)?;
         dst.write_u32::<byteorder::LittleEndian>(inode_qty as u32)?;
-        let padding = vec![0; 40];
-        dst.write_all(&padding)?;
+        dst.write_all(&self.reserved)?;
         self.root.versioned_serialize(dst, version)?;
-        dst.write_u64::<byteorder::LittleEndian>(0)?;
+        dst.write_u64::<byteorder::LittleEndian>(self.trailer)?;
         Ok(())
     }
 }
@@ -390,3 +816,327 @@ impl Serialize for Inode {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::header::{
+        Header, HeaderBuilder, Inode, InodeInfo, VirtualDirectory, RESERVED_LENGTH,
+    };
+    use crate::io::{Deserialize, Serialize};
+    use std::collections::BTreeSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn builder_round_trip() {
+        let header = HeaderBuilder::new()
+            .node("filter.txt", 100, 0xDEADBEEF)
+            .nodes([("item/item.sdata", 250, 0xCAFEBABE)])
+            .build()
+            .unwrap();
+
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        let header = Header::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+
+        let filter = header.get_inode(&"filter.txt").map(InodeInfo::from);
+        let items = header.get_inode(&"item/item.sdata").map(InodeInfo::from);
+        assert_eq!(
+            filter,
+            Some(InodeInfo {
+                offset: 0,
+                length: 100,
+                checksum: 0xDEADBEEF,
+            })
+        );
+        assert_eq!(
+            items,
+            Some(InodeInfo {
+                offset: 100,
+                length: 250,
+                checksum: 0xCAFEBABE,
+            })
+        );
+    }
+
+    #[test]
+    fn declared_file_count_mismatch() {
+        let header = HeaderBuilder::new()
+            .node("filter.txt", 100, 0)
+            .node("item/item.sdata", 250, 0)
+            .build()
+            .unwrap();
+        assert_eq!(header.declared_file_count(), None);
+
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        let header = Header::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(header.declared_file_count(), Some(2));
+
+        dst[7..11].copy_from_slice(&5u32.to_le_bytes());
+        let header = Header::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(header.declared_file_count(), Some(5));
+        assert_ne!(header.get_all_node_paths().len(), 5);
+    }
+
+    #[test]
+    fn walk_order_is_stable() {
+        let header = HeaderBuilder::new()
+            .node("data/item/item.sdata", 10, 0)
+            .node("filter.txt", 20, 0)
+            .node("data/skill.sdata", 30, 0)
+            .node("data/item/monster.sdata", 40, 0)
+            .build()
+            .unwrap();
+
+        let walk = |header: &Header| {
+            let mut visited = Vec::new();
+            header.walk(&mut |path, info| visited.push((path.to_owned(), info.offset)));
+            visited
+        };
+
+        let expected = vec![
+            ("filter.txt".to_owned(), 10),
+            ("data/skill.sdata".to_owned(), 30),
+            ("data/item/item.sdata".to_owned(), 0),
+            ("data/item/monster.sdata".to_owned(), 60),
+        ];
+        assert_eq!(walk(&header), expected);
+
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        let header = Header::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(walk(&header), expected);
+    }
+
+    #[test]
+    fn node_counts() {
+        let header = HeaderBuilder::new()
+            .node("filter.txt", 10, 0)
+            .node("data/skill.sdata", 20, 0)
+            .node("data/item/item.sdata", 30, 0)
+            .node("data/item/monster.sdata", 40, 0)
+            .build()
+            .unwrap();
+
+        assert_eq!(header.node_count(), header.get_all_node_paths().len());
+        assert_eq!(header.dir_node_count(""), Some(4));
+        assert_eq!(header.dir_node_count("data"), Some(3));
+        assert_eq!(header.dir_node_count("Data/Item"), Some(2));
+        assert_eq!(header.dir_node_count("sound"), None);
+    }
+
+    #[test]
+    fn trailer_round_trip() {
+        let header = HeaderBuilder::new()
+            .node("filter.txt", 10, 0)
+            .node("data/item.sdata", 20, 0)
+            .build()
+            .unwrap();
+
+        let mut original = Vec::new();
+        header.serialize(&mut original).unwrap();
+        let length = original.len();
+        original[length - 8..].copy_from_slice(&0x1122334455667788u64.to_le_bytes());
+
+        let header = Header::deserialize(&mut Cursor::new(original.as_slice())).unwrap();
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        assert_eq!(dst, original);
+
+        let header = Header::deserialize(&mut Cursor::new(&original[..length - 8])).unwrap();
+        assert_eq!(header.trailer, 0);
+    }
+
+    #[test]
+    fn reserved_region_round_trip() {
+        let mut header = HeaderBuilder::new()
+            .node("filter.txt", 10, 0)
+            .build()
+            .unwrap();
+        assert_eq!(header.reserved(), &[0; 40]);
+
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        assert_eq!(&dst[11..51], &[0; 40]);
+
+        let reserved = std::array::from_fn(|i| i as u8 + 1);
+        header.set_reserved(reserved);
+
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        let header = Header::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(header.reserved(), &reserved);
+
+        let mut round_trip = Vec::new();
+        header.serialize(&mut round_trip).unwrap();
+        assert_eq!(round_trip, dst);
+    }
+
+    #[test]
+    fn case_colliding_subdirectories() {
+        let mut header = Header::default();
+        for name in ["Sound", "sound"] {
+            header.root.subdirectories.push(VirtualDirectory {
+                name: name.to_owned(),
+                subdirectories: Vec::new(),
+                nodes: Vec::new(),
+            });
+        }
+
+        let err = header.root.create_subdirectory("SOUND").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert!(header.root.create_and_get_subdirectory("sound").is_err());
+
+        let node = Inode {
+            name: "bgm.wav".to_owned(),
+            offset: 0,
+            length: 10,
+            checksum: 0,
+        };
+        header.emplace_node("sound/bgm.wav", node).unwrap();
+        assert!(header.get_inode(&"SOUND/bgm.wav").is_some());
+    }
+
+    #[test]
+    fn directory_paths_match_node_prefixes() {
+        let header = HeaderBuilder::new()
+            .nodes([
+                ("filter.txt", 10, 0),
+                ("item/item.sdata", 10, 0),
+                ("data/world/2.wld", 10, 0),
+                ("data/world/3.wld", 10, 0),
+                ("data/sound/bgm.wav", 10, 0),
+            ])
+            .build()
+            .unwrap();
+
+        let mut expected = BTreeSet::new();
+        for path in header.get_all_node_paths() {
+            let mut prefix = path.as_str();
+            while let Some((parent, _)) = prefix.rsplit_once('/') {
+                expected.insert(parent.to_owned());
+                prefix = parent;
+            }
+        }
+
+        let directories = header.all_directory_paths();
+        assert_eq!(directories.len(), expected.len());
+        assert_eq!(directories.into_iter().collect::<BTreeSet<_>>(), expected);
+    }
+
+    #[test]
+    fn separators_are_normalized() {
+        let mut header = HeaderBuilder::new()
+            .node("item/item.sdata", 100, 0xDEADBEEF)
+            .build()
+            .unwrap();
+
+        let expected = header.get_inode(&"item/item.sdata").map(InodeInfo::from);
+        assert!(expected.is_some());
+        for path in [
+            "/item/item.sdata",
+            "item\\item.sdata",
+            "\\item\\item.sdata",
+            "item/item.sdata/",
+        ] {
+            assert_eq!(header.get_inode(&path).map(InodeInfo::from), expected);
+            assert!(header.get_inode_mut(&path).is_some());
+        }
+
+        let inode = Inode {
+            name: "skill.sdata".to_owned(),
+            offset: 100,
+            length: 50,
+            checksum: 0xCAFEBABE,
+        };
+        header
+            .emplace_node("\\character\\skill.sdata", inode)
+            .unwrap();
+        assert_eq!(header.all_directory_paths().len(), 2);
+        assert!(header.get_inode(&"character/skill.sdata").is_some());
+        assert_eq!(header.dir_node_count("\\character\\"), Some(1));
+        assert_eq!(header.dir_node_count("/item"), Some(1));
+
+        let removed = header.remove_inode(&"\\character\\skill.sdata");
+        assert_eq!(
+            removed.map(|inode| inode.name),
+            Some("skill.sdata".to_owned())
+        );
+        assert!(header.remove_inode(&"/item/item.sdata/").is_some());
+        assert_eq!(header.node_count(), 0);
+    }
+
+    #[test]
+    fn overlapping_nodes_are_reported() {
+        let mut header = HeaderBuilder::new()
+            .nodes([("filter.txt", 100, 0), ("item/item.sdata", 50, 0)])
+            .build()
+            .unwrap();
+        assert!(header.check_overlaps().is_empty());
+
+        let inode = Inode {
+            name: "skill.sdata".to_owned(),
+            offset: 120,
+            length: 50,
+            checksum: 0,
+        };
+        header.emplace_node("character/skill.sdata", inode).unwrap();
+        let deduplicated = Inode {
+            name: "monster.sdata".to_owned(),
+            offset: 100,
+            length: 50,
+            checksum: 0,
+        };
+        header.emplace_node("monster.sdata", deduplicated).unwrap();
+
+        let overlaps = header.check_overlaps();
+        assert_eq!(overlaps.len(), 2);
+        for (first, second) in overlaps {
+            assert_eq!(second, "character/skill.sdata");
+            assert!(first == "item/item.sdata" || first == "monster.sdata");
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut reserved = [0; RESERVED_LENGTH];
+        reserved[0] = 0x7F;
+        let mut header = HeaderBuilder::new()
+            .nodes([
+                ("filter.txt", 100, 0xDEADBEEF),
+                ("item/item.sdata", 250, 0xCAFEBABE),
+                ("character/npc/skill.sdata", 50, 0x12345678),
+            ])
+            .build()
+            .unwrap();
+        header.set_reserved(reserved);
+
+        let json = serde_json::to_string(&header).unwrap();
+        let parsed = serde_json::from_str::<Header>(&json).unwrap();
+        assert_eq!(parsed.get_all_node_paths(), header.get_all_node_paths());
+        assert_eq!(parsed.get_all_entries(), header.get_all_entries());
+        assert_eq!(parsed.reserved(), &reserved);
+    }
+
+    #[test]
+    fn overlaps_near_the_maximum_offset() {
+        let mut header = Header::default();
+        for (name, offset) in [
+            ("first.txt", usize::MAX - 10),
+            ("second.txt", usize::MAX - 5),
+        ] {
+            let inode = Inode {
+                name: name.to_owned(),
+                offset,
+                length: 100,
+                checksum: 0,
+            };
+            header.emplace_node(name, inode).unwrap();
+        }
+
+        assert_eq!(
+            header.check_overlaps(),
+            [("first.txt".to_owned(), "second.txt".to_owned())]
+        );
+    }
+}
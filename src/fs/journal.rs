@@ -0,0 +1,99 @@
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::io::{ErrorKind, Read, Write};
+
+/// The tag which precedes a [JournalEntry::Place] entry.
+const PLACE_TAG: u8 = 1;
+
+/// The tag which precedes a [JournalEntry::Remove] entry.
+const REMOVE_TAG: u8 = 2;
+
+/// A single change to the nodes of a filestore, as recorded in its journal. Replaying every entry
+/// of a journal in order over the header it was started from reproduces the latest header, even if
+/// that header was never written to disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEntry {
+    /// A node was pointed at a region of the data file, which had already been written.
+    Place {
+        path: String,
+        offset: usize,
+        length: usize,
+        checksum: u32,
+    },
+    /// A node was removed.
+    Remove { path: String },
+}
+
+/// Reads every complete entry from a journal. An entry which was only partially written, such as
+/// when the process died while appending it, ends the journal and is ignored.
+///
+/// # Arguments
+/// * `src` - The source.
+pub fn read_entries<R>(src: &mut R) -> Result<Vec<JournalEntry>, std::io::Error>
+where
+    R: Read,
+{
+    let mut entries = Vec::new();
+    loop {
+        match JournalEntry::deserialize(src) {
+            Ok(entry) => entries.push(entry),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(entries),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+impl Serialize for JournalEntry {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        match self {
+            JournalEntry::Place {
+                path,
+                offset,
+                length,
+                checksum,
+            } => {
+                dst.write_u8(PLACE_TAG)?;
+                dst.write_length_prefixed_string(path)?;
+                dst.write_u64::<LE>(*offset as u64)?;
+                dst.write_u64::<LE>(*length as u64)?;
+                dst.write_u32::<LE>(*checksum)?;
+            }
+            JournalEntry::Remove { path } => {
+                dst.write_u8(REMOVE_TAG)?;
+                dst.write_length_prefixed_string(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Deserialize for JournalEntry {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        match src.read_u8()? {
+            PLACE_TAG => Ok(JournalEntry::Place {
+                path: src.read_length_prefixed_string()?,
+                offset: src.read_u64::<LE>()? as usize,
+                length: src.read_u64::<LE>()? as usize,
+                checksum: src.read_u32::<LE>()?,
+            }),
+            REMOVE_TAG => Ok(JournalEntry::Remove {
+                path: src.read_length_prefixed_string()?,
+            }),
+            tag => Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown journal entry tag {tag}"),
+            )),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::fs::header::InodeInfo;
+
+/// A machine-readable listing of every node contained within a filestore.
+#[derive(Debug, serde::Serialize)]
+pub struct Manifest {
+    pub total_files: usize,
+    pub total_length: usize,
+    pub nodes: Vec<ManifestEntry>,
+}
+
+/// The path and metadata of a single node in a [Manifest].
+#[derive(Debug, serde::Serialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub offset: usize,
+    pub length: usize,
+    pub checksum: u32,
+}
+
+impl FromIterator<(String, InodeInfo)> for Manifest {
+    fn from_iter<T: IntoIterator<Item = (String, InodeInfo)>>(iter: T) -> Self {
+        let nodes = iter
+            .into_iter()
+            .map(|(path, info)| ManifestEntry {
+                path,
+                offset: info.offset,
+                length: info.length,
+                checksum: info.checksum,
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            total_files: nodes.len(),
+            total_length: nodes.iter().map(|node| node.length).sum(),
+            nodes,
+        }
+    }
+}
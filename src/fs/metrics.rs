@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counters describing the reads performed on a filestore since it was opened. These are only
+/// collected when the `metrics` feature is enabled.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReadMetrics {
+    /// The number of nodes which were read.
+    pub nodes_read: u64,
+    /// The total number of bytes which were read.
+    pub bytes_read: u64,
+    /// The total time spent reading.
+    pub elapsed: Duration,
+}
+
+/// The live counters behind [ReadMetrics]. These are atomic, so that reads which only borrow a
+/// filestore, such as from multiple threads at once, are recorded too.
+#[derive(Default, Debug)]
+pub(crate) struct ReadCounters {
+    nodes_read: AtomicU64,
+    bytes_read: AtomicU64,
+    elapsed_nanos: AtomicU64,
+}
+
+impl ReadCounters {
+    /// Records a read which started at a given instant. Reads of nodes which don't exist aren't
+    /// counted.
+    ///
+    /// # Arguments
+    /// * `length`  - The number of bytes which were read, if the node exists.
+    /// * `started` - When the read started.
+    pub(crate) fn record(&self, length: Option<usize>, started: Instant) {
+        if let Some(length) = length {
+            let elapsed = started.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+            self.nodes_read.fetch_add(1, Ordering::Relaxed);
+            self.bytes_read.fetch_add(length as u64, Ordering::Relaxed);
+            self.elapsed_nanos.fetch_add(elapsed, Ordering::Relaxed);
+        }
+    }
+
+    /// Takes a copy of the counters.
+    pub(crate) fn snapshot(&self) -> ReadMetrics {
+        ReadMetrics {
+            nodes_read: self.nodes_read.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            elapsed: Duration::from_nanos(self.elapsed_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
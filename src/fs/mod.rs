@@ -1,14 +1,80 @@
-use crate::fs::header::{Header, HeaderDeserializeError, Inode};
-use crate::io::{Deserialize, GameVersion, Serialize};
-use crc32fast::Hasher;
+use crate::fs::checksum::{ChecksumAlgorithm, ChecksumHasher};
+use crate::fs::header::{path_components, Header, HeaderDeserializeError, Inode, InodeInfo};
+use crate::fs::journal::JournalEntry;
+use crate::fs::manifest::Manifest;
+#[cfg(feature = "metrics")]
+use crate::fs::metrics::{ReadCounters, ReadMetrics};
+use crate::fs::types::{is_shaiya_encrypted, SData, SIGNATURE_LENGTH};
+use crate::io::{is_version_mismatch, Deserialize, GameVersion, Serialize};
 use memmap2::Mmap;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
 use std::fs::{File, OpenOptions};
-use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+use thiserror::Error;
 
+pub mod checksum;
 pub mod header;
+pub mod journal;
+pub mod manifest;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod types;
 
+#[derive(Error, Debug)]
+pub enum FilestoreError {
+    #[error("node does not exist: {0}")]
+    NodeNotFound(String),
+
+    #[error("did not match any game version ({})", format_attempts(.0))]
+    VersionMismatch(Vec<(GameVersion, std::io::Error)>),
+
+    #[error("corrupt data while decoding as {version}: {source}")]
+    Corrupt {
+        version: GameVersion,
+        source: std::io::Error,
+    },
+
+    #[error("checksum mismatch for {path} (expected {expected:#010X}, found {actual:#010X})")]
+    ChecksumMismatch {
+        path: String,
+        expected: u32,
+        actual: u32,
+    },
+
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<FilestoreError> for std::io::Error {
+    fn from(err: FilestoreError) -> Self {
+        match err {
+            FilestoreError::Io(err) => err,
+            FilestoreError::NodeNotFound(_) => std::io::Error::new(ErrorKind::NotFound, err),
+            FilestoreError::VersionMismatch(_) => std::io::Error::new(ErrorKind::InvalidInput, err),
+            FilestoreError::Corrupt { .. } => std::io::Error::new(ErrorKind::InvalidData, err),
+            FilestoreError::ChecksumMismatch { .. } => {
+                std::io::Error::new(ErrorKind::InvalidData, err)
+            }
+        }
+    }
+}
+
+/// Formats the errors from each attempted [GameVersion] into a single line.
+///
+/// # Arguments
+/// * `attempts`    - The attempted versions, and the error each failed with.
+fn format_attempts(attempts: &[(GameVersion, std::io::Error)]) -> String {
+    attempts
+        .iter()
+        .map(|(version, err)| format!("{version}: {err}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 pub trait ReadableStorage {
     /// Get the path to every node contained within the storage.
     fn all_node_paths(&self) -> Vec<String>;
@@ -22,29 +88,98 @@ pub trait ReadableStorage {
     where
         T: AsRef<str>;
 
+    /// Reads at most the first `length` bytes of a file at a given path in the virtual
+    /// filesystem. This will return `None` if a node is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    /// * `length`          - The maximum number of bytes to read.
+    fn read_prefix(&mut self, virtual_path: impl AsRef<str>, length: usize) -> Option<Vec<u8>> {
+        let mut data = self.read(virtual_path)?;
+        data.truncate(length);
+        Some(data)
+    }
+
+    /// Checks if a file at a given path in the virtual filesystem is SEED-encrypted, by reading
+    /// only its signature rather than decrypting the file. This will return `None` if a node is
+    /// not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn is_node_encrypted(&mut self, virtual_path: impl AsRef<str>) -> Option<bool> {
+        self.read_prefix(virtual_path, SIGNATURE_LENGTH)
+            .map(|prefix| is_shaiya_encrypted(&prefix))
+    }
+
+    /// Gets the metadata of a node at a given path in the virtual filesystem, without reading
+    /// its contents. This will return `None` if a node is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn stat(&self, virtual_path: impl AsRef<str>) -> Option<InodeInfo>;
+
+    /// Gets the path and metadata of every node contained within the storage. This is
+    /// considerably cheaper than calling [Self::stat] for every path in [Self::all_node_paths].
+    fn entries(&self) -> impl Iterator<Item = (String, InodeInfo)>;
+
+    /// Builds a manifest describing every node contained within the storage, which can be
+    /// serialized and distributed alongside a patch.
+    fn manifest(&self) -> Manifest {
+        self.entries().collect()
+    }
+
     /// Deserializes a file at a given path in the filesystem. This will attempt to deserialize
-    /// with every game version, until it either fails or finds a match. If you know the relevant
-    /// [GameVersion] before hand, please use [Self::read_versioned_type] and specify it
-    /// explicitly.
+    /// with every game version, starting with the one suggested by [Self::detect_version], until
+    /// it either fails or finds a match. If no version matches, the returned error contains the
+    /// reason each version failed. Only errors which suggest the wrong version was used move on
+    /// to the next version, and any other error is returned as [FilestoreError::Corrupt] straight
+    /// away (see [is_version_mismatch]). If you know the relevant [GameVersion] before hand,
+    /// please use [Self::read_versioned_type] and specify it explicitly.
     ///
     /// # Arguments
     /// * `virtual_path`    - The path to the file, relative to the root directory.
     fn read_type<T>(
         &mut self,
         virtual_path: impl AsRef<str>,
-    ) -> Result<(T, GameVersion), std::io::Error>
+    ) -> Result<(T, GameVersion), FilestoreError>
     where
         T: Deserialize<Error = std::io::Error>,
     {
-        for version in GameVersion::all() {
-            if let Ok(data) = self.read_versioned_type(virtual_path.as_ref(), *version) {
-                return Ok((data, *version));
+        let virtual_path = virtual_path.as_ref();
+        let data = self
+            .read(virtual_path)
+            .ok_or_else(|| FilestoreError::NodeNotFound(virtual_path.to_owned()))?;
+
+        let detected = T::detect_version(&data);
+        let versions = detected
+            .into_iter()
+            .chain(GameVersion::iter().filter(|version| Some(*version) != detected));
+
+        let mut attempts = Vec::with_capacity(GameVersion::all().len());
+        for version in versions {
+            let mut src = Cursor::new(data.as_slice());
+            match T::versioned_deserialize(&mut src, version) {
+                Ok(value) => return Ok((value, version)),
+                Err(err) if is_version_mismatch(&err) => attempts.push((version, err)),
+                Err(source) => return Err(FilestoreError::Corrupt { version, source }),
             }
         }
-        Err(std::io::Error::new(
-            ErrorKind::InvalidInput,
-            "did not match any game version",
-        ))
+        Err(FilestoreError::VersionMismatch(attempts))
+    }
+
+    /// Guesses the [GameVersion] a file at a given path was written with, from cues in its
+    /// structure rather than by fully decoding it with every version. This returns `None` if the
+    /// node doesn't exist, or if the version can't be determined. [Self::read_type] tries the
+    /// detected version before any other.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn detect_version<T>(&mut self, virtual_path: impl AsRef<str>) -> Option<GameVersion>
+    where
+        T: Deserialize,
+    {
+        let data = self.read(virtual_path.as_ref())?;
+        T::detect_version(&data)
     }
 
     /// Deserializes a file at a given path in the filesystem, using a specified [GameVersion]. This will
@@ -58,21 +193,30 @@ pub trait ReadableStorage {
         &mut self,
         virtual_path: impl AsRef<str>,
         version: GameVersion,
-    ) -> Result<T, std::io::Error>
+    ) -> Result<T, FilestoreError>
     where
         T: Deserialize<Error = std::io::Error>,
     {
+        let virtual_path = virtual_path.as_ref();
         match self.read(virtual_path) {
             Some(mut data) => {
                 let mut src = Cursor::new(&mut data);
-                T::versioned_deserialize(&mut src, version)
+                Ok(T::versioned_deserialize(&mut src, version)?)
             }
-            None => Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "inode does not exist",
-            )),
+            None => Err(FilestoreError::NodeNotFound(virtual_path.to_owned())),
         }
     }
+
+    /// Reads an `.SData` file at a given path in the filesystem, and returns the decrypted
+    /// payload without parsing it into a record type. Files which are not encrypted are
+    /// returned as-is.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn read_sdata(&mut self, virtual_path: impl AsRef<str>) -> Result<Vec<u8>, FilestoreError> {
+        let sdata = self.read_versioned_type::<SData>(virtual_path, *GameVersion::last())?;
+        Ok(sdata.data)
+    }
 }
 
 pub trait WritableStorage {
@@ -88,7 +232,7 @@ pub trait WritableStorage {
         virtual_path: T,
         data: &[u8],
         serialize_header: bool,
-    ) -> Result<(), std::io::Error>
+    ) -> Result<WriteOutcome, FilestoreError>
     where
         T: AsRef<str>;
 
@@ -104,13 +248,32 @@ pub trait WritableStorage {
         &mut self,
         virtual_path: impl AsRef<str>,
         typ: &T,
-    ) -> Result<(), std::io::Error>
+    ) -> Result<(), FilestoreError>
     where
         T: Serialize<Error = std::io::Error>,
     {
         self.write_versioned_type(virtual_path, typ, *GameVersion::last())
     }
 
+    /// Writes a type back to a node at a given path, with the same [GameVersion] it was read
+    /// with. This takes the value returned by [ReadableStorage::read_type], and avoids
+    /// accidentally migrating the data to the newest version when writing it back.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file node.
+    /// * `typ`             - The serializable type, and the version it was read with.
+    fn update_type<T>(
+        &mut self,
+        virtual_path: impl AsRef<str>,
+        typ: &(T, GameVersion),
+    ) -> Result<(), FilestoreError>
+    where
+        T: Serialize<Error = std::io::Error>,
+    {
+        let (typ, version) = typ;
+        self.write_versioned_type(virtual_path, typ, *version)
+    }
+
     /// Writes a serializable type to a given at a given path. If the node does not already exist, it
     /// will be created.
     ///
@@ -123,22 +286,28 @@ pub trait WritableStorage {
         virtual_path: impl AsRef<str>,
         typ: &T,
         version: GameVersion,
-    ) -> Result<(), std::io::Error>
+    ) -> Result<(), FilestoreError>
     where
         T: Serialize<Error = std::io::Error>,
     {
         let mut dst = Vec::with_capacity(10_000);
         typ.versioned_serialize(&mut dst, version)?;
 
-        self.write(virtual_path, &dst, true)
+        self.write(virtual_path, &dst, true)?;
+        Ok(())
     }
 }
 
 /// An efficient, read-only view over a filestore. This will not allow any files to be modified, and
 /// is backed by a memory-mapped view of the data file.
 pub struct ImmutableFilestore {
+    header_path: PathBuf,
+    data_path: PathBuf,
     header: Header,
     data_file: Mmap,
+    checksum_algorithm: ChecksumAlgorithm,
+    #[cfg(feature = "metrics")]
+    metrics: ReadCounters,
 }
 
 /// A filestore which supports both reading and writing of files. This uses traditional disk I/O. If
@@ -148,6 +317,234 @@ pub struct MutableFilestore {
     header_file: File,
     header: Header,
     data_file: File,
+    dedup: bool,
+    checksum_algorithm: ChecksumAlgorithm,
+    free_spans: Vec<(usize, usize)>,
+    alignment: usize,
+    journal: Option<File>,
+    mapped_reads: bool,
+    data_map: Option<Mmap>,
+    #[cfg(feature = "metrics")]
+    metrics: ReadCounters,
+}
+
+/// A filestore which is held entirely in memory, without any backing files on disk. This is
+/// primarily useful for constructing fixtures in tests.
+#[derive(Default)]
+pub struct InMemoryFilestore {
+    nodes: BTreeMap<String, Vec<u8>>,
+}
+
+/// Describes where a write placed its data.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct WriteOutcome {
+    /// If the data was written into space which was already part of the data file, such as the
+    /// node's existing space, a region freed by an earlier write, or identical deduplicated data.
+    pub reused_slot: bool,
+    /// The number of bytes the data file grew by.
+    pub bytes_appended: u64,
+}
+
+impl WriteOutcome {
+    /// The outcome of a write which reused space already in the data file.
+    fn reused() -> Self {
+        Self {
+            reused_slot: true,
+            bytes_appended: 0,
+        }
+    }
+
+    /// The outcome of a write which was appended to the end of the data file.
+    ///
+    /// # Arguments
+    /// * `length`  - The number of bytes appended.
+    fn appended(length: usize) -> Self {
+        Self {
+            reused_slot: false,
+            bytes_appended: length as u64,
+        }
+    }
+}
+
+/// The changes that [MutableFilestore::patch] would make, as planned by
+/// [MutableFilestore::patch_dry_run].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PatchPlan {
+    /// The paths of the nodes which would be created.
+    pub created: Vec<String>,
+    /// The paths of the existing nodes which would be overwritten in place.
+    pub overwritten: Vec<String>,
+    /// The paths of the existing nodes which would no longer fit in their space, and would be
+    /// moved elsewhere in the data file.
+    pub grown: Vec<String>,
+    /// The number of bytes the data file would grow by.
+    pub bytes_added: usize,
+}
+
+/// Marks a region of the data file as free, merging it with any adjacent free regions.
+///
+/// # Arguments
+/// * `free_spans`  - The free regions, ordered by offset.
+/// * `offset`      - The offset of the region.
+/// * `length`      - The length of the region.
+fn release_span(free_spans: &mut Vec<(usize, usize)>, offset: usize, length: usize) {
+    if length == 0 {
+        return;
+    }
+
+    let index = free_spans.partition_point(|(start, _)| *start < offset);
+    free_spans.insert(index, (offset, length));
+
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(free_spans.len());
+    for (start, length) in free_spans.drain(..) {
+        match merged.last_mut() {
+            Some((last_start, last_length)) if *last_start + *last_length >= start => {
+                *last_length = (*last_length).max(start + length - *last_start);
+            }
+            _ => merged.push((start, length)),
+        }
+    }
+    *free_spans = merged;
+}
+
+/// Takes space for some data from the first free region which is large enough to hold it at an
+/// aligned offset. This returns `None` if no free region is large enough, and the data should be
+/// appended to the data file. Any space skipped to reach the aligned offset remains free.
+///
+/// # Arguments
+/// * `free_spans`  - The free regions, ordered by offset.
+/// * `length`      - The length of the data.
+/// * `alignment`   - The boundary the offset must be a multiple of.
+fn allocate_span(
+    free_spans: &mut Vec<(usize, usize)>,
+    length: usize,
+    alignment: usize,
+) -> Option<usize> {
+    let (index, offset) = free_spans
+        .iter()
+        .enumerate()
+        .filter(|_| length > 0)
+        .find_map(|(index, (start, free_length))| {
+            let offset = start.next_multiple_of(alignment);
+            (offset + length <= start + free_length).then_some((index, offset))
+        })?;
+
+    let (start, free_length) = free_spans.remove(index);
+    let tail = (offset + length, start + free_length - offset - length);
+    for (span_start, span_length) in [(start, offset - start), tail].into_iter().rev() {
+        if span_length > 0 {
+            free_spans.insert(index, (span_start, span_length));
+        }
+    }
+    Some(offset)
+}
+
+/// Computes the checksum of the data a node points at within a data file which is held in memory,
+/// such as through a memory map. This is shared by every filestore which verifies checksums, so
+/// that they all treat a node the same way. This returns `None` if the node points outside of the
+/// data file.
+///
+/// # Arguments
+/// * `data_file`   - The contents of the data file.
+/// * `node`        - The node metadata.
+/// * `algorithm`   - The checksum algorithm.
+fn region_checksum(
+    data_file: &[u8],
+    node: &InodeInfo,
+    algorithm: ChecksumAlgorithm,
+) -> Option<u32> {
+    let end = node.offset.checked_add(node.length)?;
+    data_file
+        .get(node.offset..end)
+        .map(|data| algorithm.checksum(data))
+}
+
+/// The region of the data file which a node currently occupies, and how a write to the node may
+/// reuse it.
+#[derive(Debug, Copy, Clone)]
+struct ExistingRegion {
+    offset: usize,
+    length: usize,
+    /// If the region may be overwritten in place, as no other node shares it through
+    /// deduplication.
+    overwritable: bool,
+    /// If the region may be freed when the node moves, as no other node points at it.
+    releasable: bool,
+}
+
+/// Where [place_span] decided to put some data.
+#[derive(Debug, Copy, Clone)]
+struct Placement {
+    /// The offset of the data.
+    offset: usize,
+    /// The length of the node's existing region, if the data overwrites it in place.
+    overwritten_length: Option<usize>,
+    /// The number of bytes the data file grows by, including any alignment padding, if the data
+    /// is appended to the end of it.
+    appended: Option<usize>,
+}
+
+impl Placement {
+    /// Describes this placement as the outcome of a write.
+    fn outcome(&self) -> WriteOutcome {
+        match self.appended {
+            Some(length) => WriteOutcome::appended(length),
+            None => WriteOutcome::reused(),
+        }
+    }
+}
+
+/// Decides where a node's data should be placed, and updates the free regions to match. The data
+/// is written in place if it fits into the node's existing region, otherwise the existing region
+/// is released and the data takes the first free region which can hold it at an aligned offset,
+/// or is appended to the end of the data file. Both real writes and
+/// [MutableFilestore::patch_dry_run] place data through this, so that a plan always matches the
+/// patch.
+///
+/// # Arguments
+/// * `free_spans`  - The free regions, ordered by offset.
+/// * `existing`    - The region the node currently occupies, if it exists.
+/// * `length`      - The length of the data.
+/// * `alignment`   - The boundary the offset of relocated data must be a multiple of.
+/// * `end`         - The current length of the data file.
+fn place_span(
+    free_spans: &mut Vec<(usize, usize)>,
+    existing: Option<ExistingRegion>,
+    length: usize,
+    alignment: usize,
+    end: usize,
+) -> Placement {
+    match existing {
+        Some(region) if length <= region.length && region.overwritable => {
+            release_span(free_spans, region.offset + length, region.length - length);
+            Placement {
+                offset: region.offset,
+                overwritten_length: Some(region.length),
+                appended: None,
+            }
+        }
+        existing => {
+            if let Some(region) = existing.filter(|region| region.releasable) {
+                release_span(free_spans, region.offset, region.length);
+            }
+
+            match allocate_span(free_spans, length, alignment) {
+                Some(offset) => Placement {
+                    offset,
+                    overwritten_length: None,
+                    appended: None,
+                },
+                None => {
+                    let offset = end.next_multiple_of(alignment);
+                    Placement {
+                        offset,
+                        overwritten_length: None,
+                        appended: Some(offset + length - end),
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl ImmutableFilestore {
@@ -160,16 +557,189 @@ impl ImmutableFilestore {
     /// # Arguments
     /// * `header_path` - The path to the header file.
     /// * `data_path`   - The path to the data file.
-    pub fn open<P>(header_path: P, data_path: P) -> Result<Self, HeaderDeserializeError>
+    pub fn open<H, D>(header_path: H, data_path: D) -> Result<Self, HeaderDeserializeError>
     where
-        P: AsRef<Path>,
+        H: AsRef<Path>,
+        D: AsRef<Path>,
     {
         let header_path = header_path.as_ref();
         let data_path = data_path.as_ref();
 
         let header = Header::open(header_path)?;
         let data_file = unsafe { Mmap::map(&File::open(data_path)?) }?;
-        Ok(Self { header, data_file })
+        Ok(Self {
+            header_path: header_path.to_owned(),
+            data_path: data_path.to_owned(),
+            header,
+            data_file,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            #[cfg(feature = "metrics")]
+            metrics: ReadCounters::default(),
+        })
+    }
+
+    /// Opens a filestore from a base path, which the header and data file share. The `.sah` and
+    /// `.saf` extensions are appended to the base path, so `data` opens `data.sah` and `data.saf`.
+    ///
+    /// # Arguments
+    /// * `base`    - The path to the files, without their extensions.
+    pub fn open_pair(base: impl AsRef<Path>) -> Result<Self, HeaderDeserializeError> {
+        let base = base.as_ref().as_os_str();
+        let with_extension = |extension: &str| {
+            let mut path = base.to_owned();
+            path.push(extension);
+            PathBuf::from(path)
+        };
+
+        Self::open(with_extension(".sah"), with_extension(".saf"))
+    }
+
+    /// Re-reads the header and re-maps the data file from the paths this filestore was opened
+    /// with, such as after a patch has been written to them. If either file fails to load, the
+    /// filestore is left unchanged.
+    pub fn reload(&mut self) -> Result<(), HeaderDeserializeError> {
+        let header = Header::open(&self.header_path)?;
+        let data_file = unsafe { Mmap::map(&File::open(&self.data_path)?) }?;
+
+        self.header = header;
+        self.data_file = data_file;
+        Ok(())
+    }
+
+    /// Sets the algorithm used to verify the checksum of each node. This defaults to
+    /// [ChecksumAlgorithm::Crc32].
+    ///
+    /// # Arguments
+    /// * `algorithm`   - The checksum algorithm.
+    pub fn set_checksum_algorithm(&mut self, algorithm: ChecksumAlgorithm) {
+        self.checksum_algorithm = algorithm;
+    }
+
+    /// Borrows the data of a file at a given path directly from the memory-mapped data file,
+    /// without copying it. This should be preferred over [ReadableStorage::read] when the data
+    /// only needs to be parsed, and as it only needs a shared reference, it can be called from
+    /// multiple threads at once. This will return `None` if a node is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    pub fn as_slice(&self, virtual_path: impl AsRef<str>) -> Option<&[u8]> {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+
+        let data = self.node_data(virtual_path.as_ref());
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record(data.map(<[u8]>::len), started);
+        data
+    }
+
+    /// Borrows the data of a file at a given path from the memory-mapped data file, without
+    /// recording the read.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn node_data(&self, virtual_path: &str) -> Option<&[u8]> {
+        let node = self.header.get_inode(&virtual_path)?;
+        let end_offset = node.offset.checked_add(node.length)?;
+
+        // The header may reference data past the end of the data file if either has been
+        // modified or truncated, so treat it as missing rather than panicking.
+        self.data_file.get(node.offset..end_offset)
+    }
+
+    /// Borrows the data of a file at a given path, like [Self::as_slice], after checking that it
+    /// matches the node's stored checksum.
+    ///
+    /// # Errors
+    /// Returns [FilestoreError::NodeNotFound] if a node is not found at the given path, and
+    /// [FilestoreError::ChecksumMismatch] if the data doesn't match its checksum.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    pub fn read_verified(&self, virtual_path: impl AsRef<str>) -> Result<&[u8], FilestoreError> {
+        let virtual_path = virtual_path.as_ref();
+        let node = self
+            .header
+            .get_inode(&virtual_path)
+            .ok_or_else(|| FilestoreError::NodeNotFound(virtual_path.to_owned()))?;
+        let data = self
+            .as_slice(virtual_path)
+            .ok_or_else(|| std::io::Error::from(ErrorKind::UnexpectedEof))?;
+
+        let actual = self.checksum_algorithm.checksum(data);
+        if actual != node.checksum {
+            return Err(FilestoreError::ChecksumMismatch {
+                path: virtual_path.to_owned(),
+                expected: node.checksum,
+                actual,
+            });
+        }
+        Ok(data)
+    }
+
+    /// Gets the counters describing every read performed on this filestore since it was opened.
+    /// This includes data borrowed with [Self::as_slice], and nodes checked by [Self::verify].
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> ReadMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Reads every node into an owned map, keyed by path, in a single walk of the header. This
+    /// trades memory for latency, as later lookups in the map never touch the data file. Nodes
+    /// which reference data past the end of the data file are left out, as with [Self::as_slice].
+    pub fn load_all(&self) -> BTreeMap<String, Vec<u8>> {
+        self.entries()
+            .filter_map(|(path, info)| {
+                #[cfg(feature = "metrics")]
+                let started = Instant::now();
+
+                let end_offset = info.offset.checked_add(info.length)?;
+                let data = self.data_file.get(info.offset..end_offset)?.to_vec();
+
+                #[cfg(feature = "metrics")]
+                self.metrics.record(Some(data.len()), started);
+                Some((path, data))
+            })
+            .collect()
+    }
+
+    /// Verifies the checksum of every node against the contents of the data file, and returns
+    /// the paths of the nodes which did not match.
+    pub fn verify(&self) -> Vec<String> {
+        self.header
+            .get_all_entries()
+            .into_iter()
+            .filter(|(_, info)| !self.checksum_matches(info))
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Verifies the checksum of every node against the contents of the data file, and returns
+    /// the paths of the nodes which did not match. The nodes are split across the global rayon
+    /// thread pool, which is considerably faster than [Self::verify] for large filestores.
+    pub fn verify_parallel(&self) -> Vec<String> {
+        self.header
+            .get_all_entries()
+            .into_par_iter()
+            .filter(|(_, info)| !self.checksum_matches(info))
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Checks if the data for a node matches its checksum. This will return `false` if the node
+    /// points outside of the data file.
+    ///
+    /// # Arguments
+    /// * `node`    - The node metadata.
+    fn checksum_matches(&self, node: &InodeInfo) -> bool {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+
+        let checksum = region_checksum(&self.data_file, node, self.checksum_algorithm);
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record(checksum.map(|_| node.length), started);
+        checksum == Some(node.checksum)
     }
 }
 
@@ -188,13 +758,41 @@ impl ReadableStorage for ImmutableFilestore {
     where
         T: AsRef<str>,
     {
-        if let Some(node) = self.header.get_inode(&virtual_path) {
-            let end_offset = node.offset + node.length;
-            let slice = &self.data_file[node.offset..end_offset];
-            return Some(Vec::from(slice));
-        }
+        self.read_prefix(virtual_path.as_ref(), usize::MAX)
+    }
+
+    /// Reads at most the first `length` bytes of a file at a given path in the virtual
+    /// filesystem. This will return `None` if a node is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    /// * `length`          - The maximum number of bytes to read.
+    fn read_prefix(&mut self, virtual_path: impl AsRef<str>, length: usize) -> Option<Vec<u8>> {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+
+        let data = self
+            .node_data(virtual_path.as_ref())
+            .map(|data| Vec::from(&data[..length.min(data.len())]));
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record(data.as_ref().map(Vec::len), started);
+        data
+    }
+
+    /// Gets the metadata of a node at a given path in the virtual filesystem, without reading
+    /// its contents. This will return `None` if a node is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn stat(&self, virtual_path: impl AsRef<str>) -> Option<InodeInfo> {
+        self.header.get_inode(&virtual_path).map(InodeInfo::from)
+    }
 
-        None
+    /// Gets the path and metadata of every node contained within the storage. This is
+    /// considerably cheaper than calling [Self::stat] for every path in [Self::all_node_paths].
+    fn entries(&self) -> impl Iterator<Item = (String, InodeInfo)> {
+        self.header.get_all_entries().into_iter()
     }
 }
 
@@ -224,12 +822,22 @@ impl MutableFilestore {
             header_file,
             header,
             data_file,
+            dedup: false,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            free_spans: Vec::new(),
+            alignment: 1,
+            journal: None,
+            mapped_reads: false,
+            data_map: None,
+            #[cfg(feature = "metrics")]
+            metrics: ReadCounters::default(),
         })
     }
 
     /// Creates an empty filestore at a given path. If files already exist at the specified paths,
-    /// they will be overwritten. This is useful for creating patches from flat files. If you need to
-    /// update an existing filestore without losing the data, use [Self::open].
+    /// they are intentionally overwritten. This is useful for creating patches from flat files. If
+    /// you need to update an existing filestore without losing the data, use [Self::open], or use
+    /// [Self::create_new] to fail rather than overwrite existing files.
     ///
     /// # Arguments
     /// * `header_path` - The path where the header file should be created.
@@ -242,226 +850,2022 @@ impl MutableFilestore {
         let data_path = data_path.as_ref();
 
         let header_file = File::create(header_path)?;
-        let data_file = File::create(data_path)?;
+        let data_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(data_path)?;
 
         Ok(Self {
             header_file,
             header: Header::default(),
             data_file,
+            dedup: false,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            free_spans: Vec::new(),
+            alignment: 1,
+            journal: None,
+            mapped_reads: false,
+            data_map: None,
+            #[cfg(feature = "metrics")]
+            metrics: ReadCounters::default(),
         })
     }
 
-    /// Serializes the header view to the backing file.
-    fn serialize_header(&mut self) -> Result<(), std::io::Error> {
-        let mut dst = Vec::with_capacity(4_000_000);
-        self.header.serialize(&mut dst)?;
+    /// Creates an empty filestore at a given path, like [Self::create], but fails with
+    /// [ErrorKind::AlreadyExists] if either file already exists. Neither file is modified if this
+    /// fails.
+    ///
+    /// # Arguments
+    /// * `header_path` - The path where the header file should be created.
+    /// * `data_path`   - The path where the data file should be created.
+    pub fn create_new<P>(header_path: P, data_path: P) -> Result<Self, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let header_path = header_path.as_ref();
+        let data_path = data_path.as_ref();
 
-        self.header_file.set_len(0)?;
-        self.header_file.write_all(&dst)?;
-        Ok(())
+        let header_file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(header_path)?;
+        let data_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(data_path)
+            .inspect_err(|_| {
+                // Don't leave behind the header we just created.
+                let _ = std::fs::remove_file(header_path);
+            })?;
+
+        Ok(Self {
+            header_file,
+            header: Header::default(),
+            data_file,
+            dedup: false,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            free_spans: Vec::new(),
+            alignment: 1,
+            journal: None,
+            mapped_reads: false,
+            data_map: None,
+            #[cfg(feature = "metrics")]
+            metrics: ReadCounters::default(),
+        })
     }
 
-    /// Patches this filestore by taking every file from `other`, and placing it at the same path
-    /// in this filestore.
+    /// Gets the counters describing every read performed on this filestore since it was opened.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> ReadMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Sets whether writes should be deduplicated. When enabled, writing data which is identical
+    /// to an existing node will point the written node at the existing data, rather than
+    /// appending another copy to the data file.
+    ///
+    /// Nodes which share data are never overwritten in place while this is enabled, so it should
+    /// not be disabled while the filestore contains shared data that may be written to again.
     ///
     /// # Arguments
-    /// * `other`   - The storage to read from.
-    pub fn patch(&mut self, other: &mut impl ReadableStorage) -> Result<(), std::io::Error> {
-        let other_nodes = other.all_node_paths();
-        for node in &other_nodes {
-            let data = other
-                .read(node)
-                .expect("failed to read known node in other storage");
-            self.write(node, &data, false)?;
-        }
+    /// * `enabled` - If writes should be deduplicated.
+    pub fn enable_dedup(&mut self, enabled: bool) {
+        self.dedup = enabled;
+    }
 
-        self.serialize_header()?;
-        Ok(())
+    /// Sets the boundary which the offset of newly placed data is aligned to. Data appended to the
+    /// data file is preceded by zeroed padding to reach the boundary, and free regions are only
+    /// reused if they can hold the data at an aligned offset. Data which is overwritten in place
+    /// keeps its existing offset. This defaults to 1, which disables alignment, and a value of
+    /// zero is treated the same.
+    ///
+    /// # Arguments
+    /// * `alignment`   - The boundary, in bytes.
+    pub fn set_alignment(&mut self, alignment: usize) {
+        self.alignment = alignment.max(1);
     }
-}
 
-impl ReadableStorage for MutableFilestore {
-    /// Get the path to every node contained within the storage.
-    fn all_node_paths(&self) -> Vec<String> {
-        self.header.get_all_node_paths()
+    /// Sets whether reads should be served from a memory map of the data file, rather than seeking
+    /// and reading for every node. This makes repeated reads between writes nearly as fast as
+    /// with [ImmutableFilestore]. The map is dropped by every write, and recreated by the next
+    /// read.
+    ///
+    /// # Arguments
+    /// * `enabled` - If reads should use a memory map.
+    pub fn enable_mapped_reads(&mut self, enabled: bool) {
+        self.mapped_reads = enabled;
+        self.data_map = None;
     }
 
-    /// Reads a file at a given path in the virtual filesystem. This will return `None` if a node
-    /// is not found at the given path.
+    /// Sets the algorithm used to compute the checksum of each written node. This defaults to
+    /// [ChecksumAlgorithm::Crc32].
     ///
     /// # Arguments
-    /// * `virtual_path`    - The path to the file, relative to the root directory.
-    fn read<T>(&mut self, virtual_path: T) -> Option<Vec<u8>>
-    where
-        T: AsRef<str>,
-    {
-        if let Some(node) = self.header.get_inode(&virtual_path) {
-            if self
-                .data_file
-                .seek(SeekFrom::Start(node.offset as u64))
-                .is_err()
-            {
-                return None;
-            }
+    /// * `algorithm`   - The checksum algorithm.
+    pub fn set_checksum_algorithm(&mut self, algorithm: ChecksumAlgorithm) {
+        self.checksum_algorithm = algorithm;
+    }
 
-            let mut data = vec![0; node.length];
-            return match self.data_file.read(data.as_mut_slice()) {
-                Ok(_) => Some(data),
-                Err(_) => None,
-            };
-        }
+    /// Starts recording every change to the nodes of this filestore in an append-only journal, so
+    /// that the header can be rebuilt with [Self::recover_from_journal] if the process dies before
+    /// it is serialized. The journal begins with an entry for every existing node, so it can be
+    /// recovered from even if the header is lost entirely. It's emptied every time the header is
+    /// serialized, as every entry in it is then reflected by the header.
+    ///
+    /// # Arguments
+    /// * `path`    - The path to the journal file. Entries are appended if it already exists.
+    pub fn enable_journal(&mut self, path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+        let journal = OpenOptions::new().create(true).append(true).open(path)?;
+        self.journal = Some(journal);
 
-        None
+        for (path, info) in self.header.get_all_entries() {
+            self.append_journal(&JournalEntry::Place {
+                path,
+                offset: info.offset,
+                length: info.length,
+                checksum: info.checksum,
+            })?;
+        }
+        Ok(())
     }
-}
 
-impl WritableStorage for MutableFilestore {
-    /// Writes some data to a node at a given path. If the node does not already exist, it will
-    /// be created.
+    /// Opens a filestore whose header may be stale or damaged, and rebuilds the header by
+    /// replaying a journal started with [Self::enable_journal]. If the header can't be parsed,
+    /// such as when the process died while serializing it, the journal is replayed over an empty
+    /// header instead. The rebuilt header is serialized and synced before this returns, after
+    /// which the journal is truncated, as every entry in it is now reflected by the header.
     ///
     /// # Arguments
-    /// * `virtual_path`        - The path to file node.
-    /// * `data`                - The data to write.
-    /// * `serialize_header`    - If the header should be serialized. This should be set to false when writing multiple files.
-    fn write<T>(
-        &mut self,
-        virtual_path: T,
-        data: &[u8],
-        serialize_header: bool,
-    ) -> Result<(), std::io::Error>
+    /// * `header_path`     - The path to the header file.
+    /// * `data_path`       - The path to the data file.
+    /// * `journal_path`    - The path to the journal file.
+    pub fn recover_from_journal<H, D, J>(
+        header_path: H,
+        data_path: D,
+        journal_path: J,
+    ) -> Result<Self, std::io::Error>
     where
-        T: AsRef<str>,
+        H: AsRef<Path>,
+        D: AsRef<Path>,
+        J: AsRef<Path>,
     {
-        let mut hasher = Hasher::new();
-        hasher.update(data);
-        let checksum = hasher.finalize();
-
+        let header_path = header_path.as_ref();
+        let journal_path = journal_path.as_ref();
+        let header = Header::open(header_path).unwrap_or_default();
+        let header_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(header_path)?;
+        let data_file = OpenOptions::new().read(true).write(true).open(data_path)?;
+
+        let mut fs = Self {
+            header_file,
+            header,
+            data_file,
+            dedup: false,
+            checksum_algorithm: ChecksumAlgorithm::default(),
+            free_spans: Vec::new(),
+            alignment: 1,
+            journal: None,
+            mapped_reads: false,
+            data_map: None,
+            #[cfg(feature = "metrics")]
+            metrics: ReadCounters::default(),
+        };
+
+        let mut src = BufReader::new(File::open(journal_path)?);
+        for entry in journal::read_entries(&mut src)? {
+            match entry {
+                JournalEntry::Place {
+                    path,
+                    offset,
+                    length,
+                    checksum,
+                } => fs.place_node(&path, offset, length, checksum)?,
+                JournalEntry::Remove { path } => {
+                    fs.header.remove_inode(&path);
+                }
+            }
+        }
+
+        fs.serialize_header()?;
+        fs.header_file.sync_all()?;
+        File::create(journal_path)?;
+        Ok(fs)
+    }
+
+    /// Appends an entry to the journal, if journaling is enabled. The entry is written in a single
+    /// call, so that it reaches the file even if the process dies immediately afterwards.
+    ///
+    /// # Arguments
+    /// * `entry`   - The journal entry.
+    fn append_journal(&mut self, entry: &JournalEntry) -> Result<(), std::io::Error> {
+        let Some(journal) = self.journal.as_mut() else {
+            return Ok(());
+        };
+
+        let mut buf = Vec::new();
+        entry.serialize(&mut buf)?;
+        journal.write_all(&buf)
+    }
+
+    /// Finds the offset of an existing region in the data file, which has identical contents to
+    /// some data.
+    ///
+    /// # Arguments
+    /// * `data`        - The data to search for.
+    /// * `checksum`    - The checksum of the data.
+    fn find_duplicate(
+        &mut self,
+        data: &[u8],
+        checksum: u32,
+    ) -> Result<Option<usize>, std::io::Error> {
+        let candidate = self
+            .header
+            .find_inode(|node| node.length == data.len() && node.checksum == checksum)
+            .map(|node| node.offset);
+
+        let Some(offset) = candidate else {
+            return Ok(None);
+        };
+
+        let mut existing = vec![0; data.len()];
+        self.data_file.seek(SeekFrom::Start(offset as u64))?;
+        self.data_file.read_exact(&mut existing)?;
+        Ok((existing == data).then_some(offset))
+    }
+
+    /// Serializes a type directly into the data file, without first buffering the serialized data
+    /// in memory. The type is serialized once to measure its length and checksum, and then again
+    /// into the data file, so it's serialization must be deterministic. If the node already exists
+    /// and the data fits into its existing space, it is written in place, otherwise it is
+    /// appended to the end of the data file.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file node.
+    /// * `typ`             - The serializable type.
+    /// * `version`         - The game version used for serialization.
+    pub fn write_type_streaming<T>(
+        &mut self,
+        virtual_path: impl AsRef<str>,
+        typ: &T,
+        version: GameVersion,
+    ) -> Result<(), FilestoreError>
+    where
+        T: Serialize<Error = std::io::Error>,
+    {
+        let virtual_path = virtual_path.as_ref();
+        let mut measure = ChecksumWriter::new(std::io::sink(), self.checksum_algorithm.hasher());
+        typ.versioned_serialize(&mut measure, version)?;
+        let (length, checksum) = measure.finish();
+
+        let placement = self.reserve_space(virtual_path, length)?;
+        self.data_file
+            .seek(SeekFrom::Start(placement.offset as u64))?;
+
+        self.data_map = None;
+        let mut dst = BufWriter::new(&mut self.data_file);
+        typ.versioned_serialize(&mut dst, version)?;
+        if let Some(existing_length) = placement.overwritten_length {
+            dst.write_all(&vec![0; existing_length - length])?;
+        }
+        dst.flush()?;
+        drop(dst);
+
+        self.place_node(virtual_path, placement.offset, length, checksum)?;
+        self.serialize_header()?;
+        Ok(())
+    }
+
+    /// Checks if a node shares its data with any other node, as a result of deduplication. This
+    /// always returns `false` if deduplication is disabled.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the node.
+    fn shares_data(&self, virtual_path: &str) -> bool {
+        self.dedup
+            && self
+                .header
+                .get_inode(&virtual_path)
+                .is_some_and(|inode| self.is_shared_region(inode.offset))
+    }
+
+    /// Checks if more than one node points at the data starting at a given offset. Unlike
+    /// [Self::shares_data], this doesn't depend on deduplication being enabled, so it's safe to
+    /// use before releasing a region.
+    ///
+    /// # Arguments
+    /// * `offset`  - The offset of the region.
+    fn is_shared_region(&self, offset: usize) -> bool {
+        self.header
+            .count_inodes(|node| node.offset == offset && node.length > 0)
+            > 1
+    }
+
+    /// Reads a file at a given path, and checks that it matches the node's stored checksum.
+    ///
+    /// # Errors
+    /// Returns [FilestoreError::NodeNotFound] if a node is not found at the given path, and
+    /// [FilestoreError::ChecksumMismatch] if the data doesn't match its checksum.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    pub fn read_verified(
+        &mut self,
+        virtual_path: impl AsRef<str>,
+    ) -> Result<Vec<u8>, FilestoreError> {
+        let virtual_path = virtual_path.as_ref();
+        let expected = self
+            .stat(virtual_path)
+            .ok_or_else(|| FilestoreError::NodeNotFound(virtual_path.to_owned()))?
+            .checksum;
+        let data = self
+            .read(virtual_path)
+            .ok_or_else(|| std::io::Error::from(ErrorKind::UnexpectedEof))?;
+
+        let actual = self.checksum_algorithm.checksum(&data);
+        if actual != expected {
+            return Err(FilestoreError::ChecksumMismatch {
+                path: virtual_path.to_owned(),
+                expected,
+                actual,
+            });
+        }
+        Ok(data)
+    }
+
+    /// Reads every node, and returns the paths of the nodes whose data no longer matches their
+    /// stored checksum, such as after an interrupted write. Nodes which can't be read are also
+    /// treated as corrupt. Each node is hashed as it's read, rather than being buffered in full.
+    pub fn corrupt_nodes(&mut self) -> Vec<String> {
+        self.header
+            .get_all_entries()
+            .into_iter()
+            .filter(|(_, info)| self.stored_checksum(info).ok() != Some(info.checksum))
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Computes the checksum of the data a node points at, streaming it from the data file.
+    ///
+    /// # Arguments
+    /// * `node`    - The node metadata.
+    fn stored_checksum(&mut self, node: &InodeInfo) -> Result<u32, std::io::Error> {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+
+        let algorithm = self.checksum_algorithm;
+        let checksum = match self.data_map() {
+            Some(map) => region_checksum(map, node, algorithm)
+                .ok_or_else(|| std::io::Error::from(ErrorKind::UnexpectedEof)),
+            None => self
+                .data_file
+                .seek(SeekFrom::Start(node.offset as u64))
+                .and_then(|_| algorithm.checksum_reader(&mut self.data_file, node.length)),
+        };
+
+        #[cfg(feature = "metrics")]
+        self.metrics
+            .record(checksum.as_ref().ok().map(|_| node.length), started);
+        checksum
+    }
+
+    /// Finds every pair of nodes whose data regions intersect, which indicates a corrupt header.
+    /// A read of either node may return data belonging to the other.
+    pub fn check_overlaps(&self) -> Vec<(String, String)> {
+        self.header.check_overlaps()
+    }
+
+    /// Removes a node from the filestore. The space it occupied is reused by later writes, unless
+    /// it's shared with another node.
+    ///
+    /// # Arguments
+    /// * `virtual_path`        - The path to the node.
+    /// * `serialize_header`    - If the header should be serialized.
+    pub fn remove(
+        &mut self,
+        virtual_path: impl AsRef<str>,
+        serialize_header: bool,
+    ) -> Result<(), FilestoreError> {
+        let virtual_path = virtual_path.as_ref();
+        let inode = self
+            .header
+            .remove_inode(&virtual_path)
+            .ok_or_else(|| FilestoreError::NodeNotFound(virtual_path.to_owned()))?;
+        self.append_journal(&JournalEntry::Remove {
+            path: virtual_path.to_owned(),
+        })?;
+
+        // The node has already been removed, so any remaining node at this offset is sharing it.
+        if self
+            .header
+            .count_inodes(|node| node.offset == inode.offset && node.length > 0)
+            == 0
+        {
+            self.release(inode.offset, inode.length);
+        }
+
+        if serialize_header {
+            self.serialize_header()?;
+        }
+        Ok(())
+    }
+
+    /// Marks a region of the data file as free, so that it can be reused by a later write. The
+    /// free regions are only tracked for the lifetime of this filestore, and are merged with any
+    /// adjacent free regions.
+    ///
+    /// # Arguments
+    /// * `offset`  - The offset of the region.
+    /// * `length`  - The length of the region.
+    fn release(&mut self, offset: usize, length: usize) {
+        release_span(&mut self.free_spans, offset, length);
+    }
+
+    /// Describes a region which a node occupies, and how a write to the node may reuse it.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the node.
+    /// * `offset`          - The offset of the region.
+    /// * `length`          - The length of the region.
+    fn existing_region(&self, virtual_path: &str, offset: usize, length: usize) -> ExistingRegion {
+        ExistingRegion {
+            offset,
+            length,
+            overwritable: !self.shares_data(virtual_path),
+            releasable: !self.is_shared_region(offset),
+        }
+    }
+
+    /// Decides where a node's data should be placed with [place_span], and reserves the space
+    /// from the free regions of this filestore.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the node.
+    /// * `length`          - The length of the data.
+    fn reserve_space(
+        &mut self,
+        virtual_path: &str,
+        length: usize,
+    ) -> Result<Placement, std::io::Error> {
+        let existing = self
+            .header
+            .get_inode(&virtual_path)
+            .map(|inode| self.existing_region(virtual_path, inode.offset, inode.length));
+        let end = self.data_file.seek(SeekFrom::End(0))? as usize;
+        Ok(place_span(
+            &mut self.free_spans,
+            existing,
+            length,
+            self.alignment,
+            end,
+        ))
+    }
+
+    /// Points a node at a region of the data file, creating the node if it doesn't already exist.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the node.
+    /// * `offset`          - The offset of the data.
+    /// * `length`          - The length of the data.
+    /// * `checksum`        - The checksum of the data.
+    fn place_node(
+        &mut self,
+        virtual_path: &str,
+        offset: usize,
+        length: usize,
+        checksum: u32,
+    ) -> Result<(), std::io::Error> {
+        self.append_journal(&JournalEntry::Place {
+            path: virtual_path.to_owned(),
+            offset,
+            length,
+            checksum,
+        })?;
+
         if let Some(inode) = self.header.get_inode_mut(&virtual_path) {
-            let existing_space = inode.length;
-            let can_fit_into_existing_space = data.len() <= existing_space;
-            inode.length = data.len();
+            inode.offset = offset;
+            inode.length = length;
             inode.checksum = checksum;
+            return Ok(());
+        }
+
+        let name = path_components(virtual_path).pop_back().unwrap_or_default();
+        let inode = Inode {
+            name: name.to_owned(),
+            offset,
+            length,
+            checksum,
+        };
+        self.header.emplace_node(virtual_path, inode)
+    }
+
+    /// Gets the memory map of the data file, creating it if it was dropped by a write. This returns
+    /// `None` if mapped reads are disabled, or if the data file couldn't be mapped.
+    fn data_map(&mut self) -> Option<&Mmap> {
+        if self.mapped_reads && self.data_map.is_none() {
+            self.data_map = unsafe { Mmap::map(&self.data_file) }.ok();
+        }
+        self.data_map.as_ref()
+    }
+
+    /// Serializes the header view to the backing file.
+    fn serialize_header(&mut self) -> Result<(), std::io::Error> {
+        self.header_file.set_len(0)?;
+        self.header_file.seek(SeekFrom::Start(0))?;
+
+        let mut dst = BufWriter::new(&self.header_file);
+        self.header.serialize(&mut dst)?;
+        dst.flush()?;
+        drop(dst);
+
+        // The header must reach the disk before the journal is emptied, otherwise a crash could
+        // lose the changes from both.
+        if let Some(journal) = self.journal.as_mut() {
+            self.header_file.sync_all()?;
+            journal.set_len(0)?;
+        }
+        Ok(())
+    }
+
+    /// Patches this filestore by taking every file from `other`, and placing it at the same path
+    /// in this filestore.
+    ///
+    /// # Arguments
+    /// * `other`   - The storage to read from.
+    pub fn patch(&mut self, other: &mut impl ReadableStorage) -> Result<(), FilestoreError> {
+        let other_nodes = other.all_node_paths();
+        for node in &other_nodes {
+            let data = other
+                .read(node)
+                .expect("failed to read known node in other storage");
+            self.write(node, &data, false)?;
+        }
+
+        self.serialize_header()?;
+        Ok(())
+    }
+
+    /// Plans the changes that [Self::patch] would make, without writing anything. Every node is
+    /// placed exactly as a real write would place it, including reusing space freed by earlier
+    /// nodes in the same patch. Deduplication is not simulated, so with it enabled the plan is an
+    /// upper bound on the bytes added.
+    ///
+    /// # Arguments
+    /// * `other`   - The storage to read from.
+    pub fn patch_dry_run(
+        &mut self,
+        other: &mut impl ReadableStorage,
+    ) -> Result<PatchPlan, FilestoreError> {
+        let mut plan = PatchPlan::default();
+        let mut free_spans = self.free_spans.clone();
+        let mut end = self.data_file.seek(SeekFrom::End(0))? as usize;
+        let mut placed: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+
+        for node in other.all_node_paths() {
+            let length = other
+                .stat(&node)
+                .ok_or_else(|| FilestoreError::NodeNotFound(node.clone()))?
+                .length;
+            let existing = placed
+                .get(&node)
+                .copied()
+                .or_else(|| {
+                    self.header
+                        .get_inode(&node)
+                        .map(|inode| (inode.offset, inode.length))
+                })
+                .map(|(offset, length)| self.existing_region(&node, offset, length));
+
+            let placement = place_span(&mut free_spans, existing, length, self.alignment, end);
+            match (existing, placement.overwritten_length) {
+                (Some(_), Some(_)) => plan.overwritten.push(node.clone()),
+                (Some(_), None) => plan.grown.push(node.clone()),
+                (None, _) => plan.created.push(node.clone()),
+            }
+            if let Some(appended) = placement.appended {
+                plan.bytes_added += appended;
+                end += appended;
+            }
+            placed.insert(node, (placement.offset, length));
+        }
+
+        Ok(plan)
+    }
+
+    /// Patches this filestore by taking every file from an [ImmutableFilestore], like
+    /// [Self::patch]. The data is copied straight out of the other filestore's memory map, and if
+    /// both filestores use the same [ChecksumAlgorithm], the stored checksum of each node is
+    /// carried over rather than computed again.
+    ///
+    /// # Arguments
+    /// * `other`   - The filestore to read from.
+    pub fn patch_from(&mut self, other: &ImmutableFilestore) -> Result<(), FilestoreError> {
+        let compatible = other.checksum_algorithm == self.checksum_algorithm;
+        for (node, info) in other.entries() {
+            let data = other
+                .as_slice(&node)
+                .ok_or_else(|| FilestoreError::NodeNotFound(node.clone()))?;
+            let checksum = if compatible {
+                info.checksum
+            } else {
+                self.checksum_algorithm.checksum(data)
+            };
+            self.write_with_checksum(&node, data, checksum, false)?;
+        }
+
+        self.serialize_header()?;
+        Ok(())
+    }
+
+    /// Writes some data to a node at a given path, with a checksum which has already been computed
+    /// with this filestore's [ChecksumAlgorithm].
+    ///
+    /// # Arguments
+    /// * `virtual_path`        - The path to file node.
+    /// * `data`                - The data to write.
+    /// * `checksum`            - The checksum of the data.
+    /// * `serialize_header`    - If the header should be serialized.
+    fn write_with_checksum(
+        &mut self,
+        virtual_path: &str,
+        data: &[u8],
+        checksum: u32,
+        serialize_header: bool,
+    ) -> Result<WriteOutcome, FilestoreError> {
+        if self.dedup {
+            if let Some(offset) = self.find_duplicate(data, checksum)? {
+                self.place_node(virtual_path, offset, data.len(), checksum)?;
+                if serialize_header {
+                    self.serialize_header()?;
+                }
+                return Ok(WriteOutcome::reused());
+            }
+        }
+
+        self.data_map = None;
+        let placement = self.reserve_space(virtual_path, data.len())?;
+        self.data_file
+            .seek(SeekFrom::Start(placement.offset as u64))?;
+        match placement.overwritten_length {
+            Some(existing_length) => {
+                let mut file_buf = vec![0; existing_length];
+                file_buf[..data.len()].copy_from_slice(data);
+                self.data_file.write_all(&file_buf)?;
+            }
+            None => self.data_file.write_all(data)?,
+        }
+
+        self.place_node(virtual_path, placement.offset, data.len(), checksum)?;
+
+        if serialize_header {
+            self.serialize_header()?;
+        }
+
+        Ok(placement.outcome())
+    }
+
+    /// Patches this filestore by taking every file from `other`, like [Self::patch], but skips any
+    /// file whose length and checksum already match the node at the same path in this filestore.
+    /// Returns the number of files which were written.
+    ///
+    /// # Arguments
+    /// * `other`   - The storage to read from.
+    pub fn patch_changed_only(
+        &mut self,
+        other: &mut impl ReadableStorage,
+    ) -> Result<usize, FilestoreError> {
+        let mut written = 0;
+        for node in other.all_node_paths() {
+            let data = other
+                .read(&node)
+                .expect("failed to read known node in other storage");
+
+            let checksum = self.checksum_algorithm.checksum(&data);
+            let unchanged = self
+                .header
+                .get_inode(&node)
+                .is_some_and(|inode| inode.length == data.len() && inode.checksum == checksum);
+            if !unchanged {
+                self.write(&node, &data, false)?;
+                written += 1;
+            }
+        }
+
+        self.serialize_header()?;
+        Ok(written)
+    }
+}
+
+impl ReadableStorage for MutableFilestore {
+    /// Get the path to every node contained within the storage.
+    fn all_node_paths(&self) -> Vec<String> {
+        self.header.get_all_node_paths()
+    }
+
+    /// Reads a file at a given path in the virtual filesystem. This will return `None` if a node
+    /// is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn read<T>(&mut self, virtual_path: T) -> Option<Vec<u8>>
+    where
+        T: AsRef<str>,
+    {
+        self.read_prefix(virtual_path.as_ref(), usize::MAX)
+    }
+
+    /// Reads at most the first `length` bytes of a file at a given path in the virtual
+    /// filesystem. This will return `None` if a node is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    /// * `length`          - The maximum number of bytes to read.
+    fn read_prefix(&mut self, virtual_path: impl AsRef<str>, length: usize) -> Option<Vec<u8>> {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+
+        let node = self.header.get_inode(&virtual_path)?;
+        let offset = node.offset;
+        let length = length.min(node.length);
+
+        let data = match self.data_map() {
+            Some(map) => map.get(offset..offset.checked_add(length)?)?.to_vec(),
+            None => {
+                self.data_file.seek(SeekFrom::Start(offset as u64)).ok()?;
+                let mut data = vec![0; length];
+                self.data_file.read_exact(&mut data).ok()?;
+                data
+            }
+        };
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record(Some(data.len()), started);
+        Some(data)
+    }
+
+    /// Gets the metadata of a node at a given path in the virtual filesystem, without reading
+    /// its contents. This will return `None` if a node is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn stat(&self, virtual_path: impl AsRef<str>) -> Option<InodeInfo> {
+        self.header.get_inode(&virtual_path).map(InodeInfo::from)
+    }
+
+    /// Gets the path and metadata of every node contained within the storage. This is
+    /// considerably cheaper than calling [Self::stat] for every path in [Self::all_node_paths].
+    fn entries(&self) -> impl Iterator<Item = (String, InodeInfo)> {
+        self.header.get_all_entries().into_iter()
+    }
+}
+
+impl WritableStorage for MutableFilestore {
+    /// Writes some data to a node at a given path. If the node does not already exist, it will
+    /// be created.
+    ///
+    /// # Arguments
+    /// * `virtual_path`        - The path to file node.
+    /// * `data`                - The data to write.
+    /// * `serialize_header`    - If the header should be serialized. This should be set to false when writing multiple files.
+    fn write<T>(
+        &mut self,
+        virtual_path: T,
+        data: &[u8],
+        serialize_header: bool,
+    ) -> Result<WriteOutcome, FilestoreError>
+    where
+        T: AsRef<str>,
+    {
+        let checksum = self.checksum_algorithm.checksum(data);
+        self.write_with_checksum(virtual_path.as_ref(), data, checksum, serialize_header)
+    }
+}
+
+impl InMemoryFilestore {
+    /// Creates an empty in-memory filestore.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the key a node is stored under. The path is normalized in the same way as the header
+    /// of a filestore on disk, so `item\item.sdata` and `/item/item.sdata` both refer to the same
+    /// node as `item/item.sdata`.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the node.
+    fn node_key(virtual_path: &str) -> String {
+        Vec::from(path_components(virtual_path)).join("/")
+    }
+
+    /// Patches this filestore by taking every file from `other`, and placing it at the same path
+    /// in this filestore.
+    ///
+    /// # Arguments
+    /// * `other`   - The storage to read from.
+    pub fn patch(&mut self, other: &mut impl ReadableStorage) -> Result<(), FilestoreError> {
+        for node in other.all_node_paths() {
+            let data = other
+                .read(&node)
+                .expect("failed to read known node in other storage");
+            self.write(node, &data, false)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ReadableStorage for InMemoryFilestore {
+    /// Get the path to every node contained within the storage.
+    fn all_node_paths(&self) -> Vec<String> {
+        self.nodes.keys().cloned().collect()
+    }
+
+    /// Reads a file at a given path in the virtual filesystem. This will return `None` if a node
+    /// is not found at the given path.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn read<T>(&mut self, virtual_path: T) -> Option<Vec<u8>>
+    where
+        T: AsRef<str>,
+    {
+        self.nodes
+            .get(&Self::node_key(virtual_path.as_ref()))
+            .cloned()
+    }
+
+    /// Gets the metadata of a node at a given path in the virtual filesystem, without reading
+    /// its contents. As the nodes are not stored contiguously, the offset is always zero.
+    ///
+    /// # Arguments
+    /// * `virtual_path`    - The path to the file, relative to the root directory.
+    fn stat(&self, virtual_path: impl AsRef<str>) -> Option<InodeInfo> {
+        let key = Self::node_key(virtual_path.as_ref());
+        self.nodes.get(&key).map(|data| InodeInfo {
+            offset: 0,
+            length: data.len(),
+            checksum: crc32fast::hash(data),
+        })
+    }
+
+    /// Gets the path and metadata of every node contained within the storage. This is
+    /// considerably cheaper than calling [Self::stat] for every path in [Self::all_node_paths].
+    fn entries(&self) -> impl Iterator<Item = (String, InodeInfo)> {
+        self.nodes.iter().map(|(path, data)| {
+            let info = InodeInfo {
+                offset: 0,
+                length: data.len(),
+                checksum: crc32fast::hash(data),
+            };
+            (path.clone(), info)
+        })
+    }
+}
+
+impl WritableStorage for InMemoryFilestore {
+    /// Writes some data to a node at a given path. If the node already exists, it will be
+    /// overwritten.
+    ///
+    /// # Arguments
+    /// * `virtual_path`        - The path to file node.
+    /// * `data`                - The data to write.
+    /// * `serialize_header`    - Unused, as there is no header to serialize.
+    fn write<T>(
+        &mut self,
+        virtual_path: T,
+        data: &[u8],
+        _serialize_header: bool,
+    ) -> Result<WriteOutcome, FilestoreError>
+    where
+        T: AsRef<str>,
+    {
+        let previous = self
+            .nodes
+            .insert(Self::node_key(virtual_path.as_ref()), data.to_vec());
+        Ok(match previous {
+            Some(previous) if data.len() <= previous.len() => WriteOutcome::reused(),
+            _ => WriteOutcome::appended(data.len()),
+        })
+    }
+}
+
+/// A writer which computes the length and checksum of all the data written through it.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: ChecksumHasher,
+    length: usize,
+}
+
+impl<W> ChecksumWriter<W> {
+    /// Wraps a writer.
+    ///
+    /// # Arguments
+    /// * `inner`   - The writer to pass data through to.
+    /// * `hasher`  - The hasher used to compute the checksum.
+    fn new(inner: W, hasher: ChecksumHasher) -> Self {
+        Self {
+            inner,
+            hasher,
+            length: 0,
+        }
+    }
+
+    /// Gets the length and checksum of the data that was written.
+    fn finish(self) -> (usize, u32) {
+        (self.length, self.hasher.finalize())
+    }
+}
+
+impl<W> Write for ChecksumWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.length += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::checksum::ChecksumAlgorithm;
+    use crate::fs::header::HeaderBuilder;
+    use crate::fs::types::{ItemData, ItemRecord, SData, SkillData, SkillRecord};
+    use crate::fs::{
+        FilestoreError, ImmutableFilestore, InMemoryFilestore, MutableFilestore, ReadableStorage,
+        WritableStorage,
+    };
+    use crate::io::{Deserialize, GameVersion, Serialize};
+    use std::collections::BTreeMap;
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+    use std::path::PathBuf;
+
+    /// Gets a unique header and data file path in the system's temporary directory.
+    fn temp_paths(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir();
+        let prefix = format!("epeiros-{}-{name}", std::process::id());
+        (
+            dir.join(format!("{prefix}.sah")),
+            dir.join(format!("{prefix}.saf")),
+        )
+    }
+
+    #[test]
+    fn test() {
+        let mut fs = ImmutableFilestore::open("res/data.sah", "res/data.saf")
+            .expect("failed to open filestore");
+        assert!(fs.read_type::<String>("filter.txt").is_ok())
+    }
+
+    #[test]
+    fn sdata_write() {
+        let mut fs = ImmutableFilestore::open("res/data.sah", "res/data.saf")
+            .expect("failed to open filestore");
+        let (items, _version) = fs.read_type::<ItemData>("item/item.sdata").unwrap();
+
+        std::fs::write("res/items.json", serde_json::to_vec_pretty(&items).unwrap()).unwrap();
+        let mut out = Vec::new();
+        items
+            .versioned_serialize(&mut out, GameVersion::Ep6)
+            .unwrap();
+
+        std::fs::write("res/Item.SData", &out).unwrap();
+
+        let skills = fs
+            .read_versioned_type::<SkillData>("character/skill.sdata", GameVersion::Ep6)
+            .unwrap();
+
+        std::fs::write(
+            "res/skills.json",
+            serde_json::to_vec_pretty(&skills).unwrap(),
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        skills
+            .versioned_serialize(&mut out, GameVersion::Ep6)
+            .unwrap();
+
+        std::fs::write("res/Skill.SData", &out).unwrap();
+    }
+
+    #[test]
+    fn sdata_read() {
+        let indata = std::fs::read("res/Skill.SData").unwrap();
+        let mut src = Cursor::new(indata.as_slice());
+        let sdata = SkillData::versioned_deserialize(&mut src, GameVersion::Ep6).unwrap();
+
+        std::fs::write(
+            "res/skillsout.json",
+            serde_json::to_vec_pretty(&sdata).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn item_test() {
+        let item = std::fs::read("res/KreonItem.SData").unwrap();
+        let mut src = Cursor::new(item.as_slice());
+
+        let items = ItemData::versioned_deserialize(&mut src, GameVersion::Ep6v2).unwrap();
+        std::fs::write(
+            "res/kreonitems.json",
+            serde_json::to_vec_pretty(&items).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn patch_test() {
+        let mut other = ImmutableFilestore::open("res/data.sah", "res/data.saf").unwrap();
+        let mut fs =
+            MutableFilestore::create("res/new_with_crc.sah", "res/new_with_crc.saf").unwrap();
+        fs.patch(&mut other).unwrap();
+    }
+
+    #[test]
+    fn in_memory_patch() {
+        let mut other = InMemoryFilestore::new();
+        other.write("filter.txt", b"filter", false).unwrap();
+        other.write("item/item.sdata", &[1, 2, 3], false).unwrap();
+
+        let mut fs = InMemoryFilestore::new();
+        fs.write("filter.txt", b"old", false).unwrap();
+        fs.patch(&mut other).unwrap();
+
+        assert_eq!(fs.all_node_paths(), other.all_node_paths());
+        for path in other.all_node_paths() {
+            assert_eq!(fs.read(&path), other.read(&path));
+        }
+    }
+
+    #[test]
+    fn read_plain_sdata() {
+        let mut payload = 2u32.to_le_bytes().to_vec();
+        payload.extend_from_slice(&[0; 8]);
+
+        let mut fs = InMemoryFilestore::new();
+        fs.write("item/item.sdata", &payload, false).unwrap();
+
+        let data = fs.read_sdata("item/item.sdata").unwrap();
+        assert_eq!(u32::from_le_bytes(data[..4].try_into().unwrap()), 2);
+        assert!(fs.read_sdata("item/missing.sdata").is_err());
+    }
+
+    #[test]
+    fn parallel_verify_matches_serial() {
+        let (header_path, data_path) = temp_paths("verify");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("item/item.sdata", &[1; 64], false).unwrap();
+        fs.write("filter.txt", &[2; 64], false).unwrap();
+        fs.write("character/skill.sdata", &[3; 64], true).unwrap();
+
+        let mut data = std::fs::read(&data_path).unwrap();
+        data[0] ^= 0xFF;
+        std::fs::write(&data_path, &data).unwrap();
+
+        let fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        let serial = fs.verify();
+        assert_eq!(serial, vec!["item/item.sdata".to_owned()]);
+        assert_eq!(fs.verify_parallel(), serial);
+    }
+
+    #[test]
+    fn dedup_identical_writes() {
+        let (header_path, data_path) = temp_paths("dedup");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.enable_dedup(true);
+
+        let payload = vec![7; 1024];
+        fs.write("en/item.sdata", &payload, false).unwrap();
+        fs.write("de/item.sdata", &payload, true).unwrap();
+        assert_eq!(std::fs::metadata(&data_path).unwrap().len(), 1024);
+
+        fs.write("de/item.sdata", &[8; 16], true).unwrap();
+        assert_eq!(fs.read("en/item.sdata").unwrap(), payload);
+        assert_eq!(fs.read("de/item.sdata").unwrap(), vec![8; 16]);
+    }
+
+    #[test]
+    fn stat_matches_read() {
+        let (header_path, data_path) = temp_paths("stat");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", &[1; 32], false).unwrap();
+        fs.write("item/item.sdata", &[2; 100], true).unwrap();
+
+        let mut fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        let info = fs.stat("item/item.sdata").unwrap();
+        assert_eq!(info.offset, 32);
+        assert_eq!(info.checksum, crc32fast::hash(&[2; 100]));
+        assert_eq!(info.length, fs.read("item/item.sdata").unwrap().len());
+        assert!(fs.stat("item/missing.sdata").is_none());
+    }
+
+    #[test]
+    fn missing_node_error() {
+        let mut fs = InMemoryFilestore::new();
+        let err = fs.read_type::<String>("filter.txt").unwrap_err();
+        assert!(matches!(err, FilestoreError::NodeNotFound(_)));
+
+        let err = fs
+            .read_versioned_type::<String>("filter.txt", GameVersion::Ep4)
+            .unwrap_err();
+        assert!(matches!(err, FilestoreError::NodeNotFound(ref path) if path == "filter.txt"));
+
+        let err = std::io::Error::from(err);
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn version_mismatch_lists_attempts() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&[4, 0, 0, 0, b'a', b'b']);
+
+        let mut fs = InMemoryFilestore::new();
+        fs.write("item/item.sdata", &payload, false).unwrap();
+
+        let err = fs.read_type::<ItemData>("item/item.sdata").unwrap_err();
+        let FilestoreError::VersionMismatch(ref attempts) = err else {
+            panic!("expected a version mismatch, found {err:?}");
+        };
+        assert_eq!(attempts.len(), GameVersion::all().len());
+
+        let message = err.to_string();
+        for version in GameVersion::all() {
+            assert!(message.contains(&format!("{version}: ")));
+        }
+    }
+
+    #[test]
+    fn entries_match_paths() {
+        let (header_path, data_path) = temp_paths("entries");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", &[1; 8], false).unwrap();
+        fs.write("item/item.sdata", &[2; 8], false).unwrap();
+        fs.write("character/skill.sdata", &[3; 8], true).unwrap();
+
+        let fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        assert_eq!(fs.entries().count(), fs.all_node_paths().len());
+        for (path, info) in fs.entries() {
+            assert_eq!(fs.stat(&path), Some(info));
+        }
+    }
+
+    #[test]
+    fn additive_checksum_round_trip() {
+        let (header_path, data_path) = temp_paths("additive");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.set_checksum_algorithm(ChecksumAlgorithm::Additive);
+        fs.write("filter.txt", &[1, 2, 3], false).unwrap();
+        fs.write("item/item.sdata", &[0xFF; 32], true).unwrap();
+        assert_eq!(fs.stat("filter.txt").unwrap().checksum, 6);
+
+        let mut fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        assert_eq!(fs.verify().len(), 2);
 
-            if can_fit_into_existing_space {
-                self.data_file.seek(SeekFrom::Start(inode.offset as u64))?;
+        fs.set_checksum_algorithm(ChecksumAlgorithm::Additive);
+        assert!(fs.verify().is_empty());
+    }
 
-                let mut file_buf = vec![0; existing_space];
-                file_buf[..data.len()].copy_from_slice(data);
-                self.data_file.write_all(&file_buf)?;
-            } else {
-                let offset = self.data_file.seek(SeekFrom::End(0))?;
-                inode.offset = offset as usize;
+    #[test]
+    fn mutable_read_is_complete() {
+        let (header_path, data_path) = temp_paths("large");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
 
-                self.data_file.set_len(offset + (inode.length as u64))?;
-                self.data_file.write_all(data)?;
-            }
-            if serialize_header {
-                self.serialize_header()?;
-            }
+        let payload = (0..3_000_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        fs.write("model/large.3dc", &payload, true).unwrap();
+        assert_eq!(fs.read("model/large.3dc").unwrap(), payload);
+    }
 
-            return Ok(());
+    #[test]
+    fn read_past_end_of_data_file() {
+        let (header_path, data_path) = temp_paths("truncated");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", &[1; 16], false).unwrap();
+        fs.write("item/item.sdata", &[2; 64], true).unwrap();
+
+        let data_file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&data_path)
+            .unwrap();
+        data_file.set_len(32).unwrap();
+
+        let mut fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        assert_eq!(fs.read("filter.txt").unwrap(), vec![1; 16]);
+        assert!(fs.read("item/item.sdata").is_none());
+    }
+
+    #[test]
+    fn update_type_keeps_version() {
+        let items = serde_json::from_str::<ItemData>(r#"{"1":[{"name":"Sword"}]}"#).unwrap();
+        let mut fs = InMemoryFilestore::new();
+        fs.write_versioned_type("item/item.sdata", &items, GameVersion::Ep5)
+            .unwrap();
+        let original = fs.read("item/item.sdata").unwrap();
+
+        let read = fs.read_type::<ItemData>("item/item.sdata").unwrap();
+        assert_eq!(read.1, GameVersion::Ep5);
+
+        fs.update_type("item/item.sdata", &read).unwrap();
+        assert_eq!(fs.read("item/item.sdata").unwrap(), original);
+        assert_eq!(
+            fs.read_type::<ItemData>("item/item.sdata").unwrap().1,
+            GameVersion::Ep5
+        );
+    }
+
+    #[test]
+    fn manifest_lists_every_node() {
+        let mut fs = InMemoryFilestore::new();
+        fs.write("filter.txt", &[1; 10], false).unwrap();
+        fs.write("item/item.sdata", &[2; 20], false).unwrap();
+
+        let manifest = fs.manifest();
+        assert_eq!(manifest.nodes.len(), fs.all_node_paths().len());
+        assert_eq!(manifest.total_files, 2);
+        assert_eq!(manifest.total_length, 30);
+
+        let json = serde_json::to_value(&manifest).unwrap();
+        assert_eq!(json["nodes"][1]["path"], "item/item.sdata");
+        assert_eq!(json["nodes"][1]["length"], 20);
+    }
+
+    #[test]
+    fn streaming_write_matches_buffered() {
+        let small = serde_json::from_str::<ItemData>(r#"{"1":[{"name":"Sword"}]}"#).unwrap();
+        let large = serde_json::from_str::<ItemData>(
+            r#"{"1":[{"name":"Sword"},{"name":"Shield"}],"2":[{"name":"Bow"}]}"#,
+        )
+        .unwrap();
+
+        let (buffered_header, buffered_data) = temp_paths("buffered");
+        let mut buffered = MutableFilestore::create(&buffered_header, &buffered_data).unwrap();
+        let (streamed_header, streamed_data) = temp_paths("streamed");
+        let mut streamed = MutableFilestore::create(&streamed_header, &streamed_data).unwrap();
+
+        for items in [&large, &small, &large] {
+            buffered
+                .write_versioned_type("item/item.sdata", items, GameVersion::Ep6)
+                .unwrap();
+            streamed
+                .write_type_streaming("item/item.sdata", items, GameVersion::Ep6)
+                .unwrap();
+
+            assert_eq!(
+                std::fs::read(&buffered_data).unwrap(),
+                std::fs::read(&streamed_data).unwrap()
+            );
+            assert_eq!(
+                std::fs::read(&buffered_header).unwrap(),
+                std::fs::read(&streamed_header).unwrap()
+            );
         }
+    }
 
-        let virtual_path = virtual_path.as_ref();
-        let name = virtual_path.split('/').last().unwrap();
-        let offset = self.data_file.seek(SeekFrom::End(0))?;
+    #[test]
+    fn streamed_header_matches_buffered() {
+        let (header_path, data_path) = temp_paths("header");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
 
-        let inode = Inode {
-            name: name.to_owned(),
-            offset: offset as usize,
-            length: data.len(),
-            checksum,
-        };
+        for name in ["filter.txt", "item/item.sdata", "item/monster.sdata"] {
+            fs.write(name, name.as_bytes(), true).unwrap();
 
-        self.data_file.set_len(offset + (inode.length as u64))?;
-        self.data_file.write_all(data)?;
+            let mut buffered = Vec::new();
+            fs.header.serialize(&mut buffered).unwrap();
+            assert_eq!(std::fs::read(&header_path).unwrap(), buffered);
+        }
+    }
 
-        self.header.emplace_node(virtual_path, inode)?;
+    #[test]
+    fn reload_sees_new_files() {
+        let (header_path, data_path) = temp_paths("reload");
+        let mut writer = MutableFilestore::create(&header_path, &data_path).unwrap();
+        writer.write("filter.txt", &[1; 16], true).unwrap();
 
-        if serialize_header {
-            self.serialize_header()?;
+        let mut fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        assert!(fs.read("item/item.sdata").is_none());
+
+        writer.write("item/item.sdata", &[2; 64], true).unwrap();
+        fs.reload().unwrap();
+        assert_eq!(fs.read("item/item.sdata").unwrap(), vec![2; 64]);
+        assert_eq!(fs.read("filter.txt").unwrap(), vec![1; 16]);
+    }
+
+    #[test]
+    fn decoded_version_is_retained() {
+        let mut fs = InMemoryFilestore::new();
+        fs.write_versioned_type("item/item.sdata", &ItemData::default(), GameVersion::Ep5)
+            .unwrap();
+        fs.write_versioned_type("skill.sdata", &SkillData::default(), GameVersion::Ep5)
+            .unwrap();
+
+        let items = fs
+            .read_versioned_type::<ItemData>("item/item.sdata", GameVersion::Ep5)
+            .unwrap();
+        assert_eq!(items.version(), Some(GameVersion::Ep5));
+
+        let skills = fs
+            .read_versioned_type::<SkillData>("skill.sdata", GameVersion::Ep5)
+            .unwrap();
+        assert_eq!(skills.version(), Some(GameVersion::Ep5));
+        assert_eq!(ItemData::default().version(), None);
+    }
+
+    #[test]
+    fn patch_changed_only_skips_identical() {
+        let mut other = InMemoryFilestore::new();
+        other.write("filter.txt", b"filter", false).unwrap();
+        other.write("item/item.sdata", &[1; 128], false).unwrap();
+
+        let (header_path, data_path) = temp_paths("quick-patch");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        assert_eq!(fs.patch_changed_only(&mut other).unwrap(), 2);
+        let length = std::fs::metadata(&data_path).unwrap().len();
+
+        assert_eq!(fs.patch_changed_only(&mut other).unwrap(), 0);
+        assert_eq!(std::fs::metadata(&data_path).unwrap().len(), length);
+
+        other.write("filter.txt", b"changed", false).unwrap();
+        assert_eq!(fs.patch_changed_only(&mut other).unwrap(), 1);
+        assert_eq!(fs.read("filter.txt").unwrap(), b"changed");
+    }
+
+    #[test]
+    fn as_slice_matches_read() {
+        let (header_path, data_path) = temp_paths("as-slice");
+        let mut writer = MutableFilestore::create(&header_path, &data_path).unwrap();
+        writer.write("filter.txt", b"filter", false).unwrap();
+        writer.write("item/item.sdata", &[3; 256], true).unwrap();
+
+        let mut fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        for path in fs.all_node_paths() {
+            let length = fs.stat(&path).unwrap().length;
+            let owned = fs.read(&path).unwrap();
+
+            let slice = fs.as_slice(&path).unwrap();
+            assert_eq!(slice.len(), length);
+            assert_eq!(slice, owned.as_slice());
         }
+        assert!(fs.as_slice("missing.txt").is_none());
+    }
 
-        Ok(())
+    #[test]
+    fn write_reuses_freed_space() {
+        let (header_path, data_path) = temp_paths("free-list");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("large.bin", &[1; 1024], false).unwrap();
+        fs.write("filter.txt", &[2; 16], true).unwrap();
+        let length = std::fs::metadata(&data_path).unwrap().len();
+
+        fs.remove("large.bin", true).unwrap();
+        assert!(fs.read("large.bin").is_none());
+        assert!(matches!(
+            fs.remove("large.bin", true),
+            Err(FilestoreError::NodeNotFound(_))
+        ));
+
+        fs.write("small.bin", &[3; 512], true).unwrap();
+        fs.write("filter.txt", &[4; 8], true).unwrap();
+        fs.write("tail.bin", &[5; 8], true).unwrap();
+        assert_eq!(std::fs::metadata(&data_path).unwrap().len(), length);
+
+        assert_eq!(fs.read("small.bin").unwrap(), vec![3; 512]);
+        assert_eq!(fs.read("filter.txt").unwrap(), vec![4; 8]);
+        assert_eq!(fs.read("tail.bin").unwrap(), vec![5; 8]);
+        assert_eq!(fs.stat("small.bin").unwrap().offset, 0);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::fs::types::{ItemData, SkillData};
-    use crate::fs::{ImmutableFilestore, MutableFilestore, ReadableStorage};
-    use crate::io::{Deserialize, GameVersion, Serialize};
-    use std::io::Cursor;
+    #[test]
+    fn corrupt_nodes_are_flagged() {
+        let (header_path, data_path) = temp_paths("corrupt");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", &[1; 32], false).unwrap();
+        fs.write("item/item.sdata", &[2; 32], true).unwrap();
+        assert!(fs.corrupt_nodes().is_empty());
+
+        let offset = fs.stat("item/item.sdata").unwrap().offset as u64;
+        let mut data_file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&data_path)
+            .unwrap();
+        data_file.seek(SeekFrom::Start(offset + 4)).unwrap();
+        data_file.write_all(&[0xFF; 4]).unwrap();
+        drop(data_file);
+
+        assert_eq!(fs.corrupt_nodes(), ["item/item.sdata"]);
+    }
 
     #[test]
-    fn test() {
-        let mut fs = ImmutableFilestore::open("res/data.sah", "res/data.saf")
-            .expect("failed to open filestore");
-        assert!(fs.read_type::<String>("filter.txt").is_ok())
+    fn recover_from_journal_after_crash() {
+        let (header_path, data_path) = temp_paths("journal");
+        let journal_path = header_path.with_extension("journal");
+        let _ = std::fs::remove_file(&journal_path);
+
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("kept.txt", b"kept", true).unwrap();
+        fs.enable_journal(&journal_path).unwrap();
+        fs.write("data/new.txt", b"new", false).unwrap();
+        fs.write("kept.txt", b"changed", false).unwrap();
+        fs.write("gone.txt", b"gone", false).unwrap();
+        fs.remove("gone.txt", false).unwrap();
+        drop(fs);
+
+        // The process died part way through serializing the header, and while appending to the
+        // journal.
+        let header_file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&header_path)
+            .unwrap();
+        header_file.set_len(16).unwrap();
+        let mut journal = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal_path)
+            .unwrap();
+        journal.write_all(&[1, 32, 0]).unwrap();
+        assert!(ImmutableFilestore::open(&header_path, &data_path).is_err());
+
+        let journal_path = journal_path.to_str().unwrap();
+        let mut fs =
+            MutableFilestore::recover_from_journal(&header_path, data_path.as_path(), journal_path)
+                .unwrap();
+        assert_eq!(std::fs::metadata(journal_path).unwrap().len(), 0);
+        assert_eq!(fs.read("kept.txt").unwrap(), b"changed");
+        assert_eq!(fs.read("data/new.txt").unwrap(), b"new");
+        assert!(fs.read("gone.txt").is_none());
+        assert!(fs.corrupt_nodes().is_empty());
+
+        let fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        assert_eq!(fs.all_node_paths(), ["kept.txt", "data/new.txt"]);
     }
 
     #[test]
-    fn sdata_write() {
-        let mut fs = ImmutableFilestore::open("res/data.sah", "res/data.saf")
-            .expect("failed to open filestore");
-        let (items, _version) = fs.read_type::<ItemData>("item/item.sdata").unwrap();
+    fn journal_is_emptied_by_serializing_the_header() {
+        let (header_path, data_path) = temp_paths("journal-truncate");
+        let journal_path = header_path.with_extension("journal");
+        let _ = std::fs::remove_file(&journal_path);
 
-        std::fs::write("res/items.json", serde_json::to_vec_pretty(&items).unwrap()).unwrap();
-        let mut out = Vec::new();
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.enable_journal(&journal_path).unwrap();
+        fs.write("filter.txt", b"filter", false).unwrap();
+        assert!(std::fs::metadata(&journal_path).unwrap().len() > 0);
+
+        fs.write("item/item.sdata", b"items", true).unwrap();
+        assert_eq!(std::fs::metadata(&journal_path).unwrap().len(), 0);
+
+        // Entries written after the truncation are still appended, and can be recovered from.
+        fs.remove("filter.txt", false).unwrap();
+        assert!(std::fs::metadata(&journal_path).unwrap().len() > 0);
+        drop(fs);
+        let fs = MutableFilestore::recover_from_journal(&header_path, &data_path, &journal_path)
+            .unwrap();
+        assert_eq!(fs.all_node_paths(), ["item/item.sdata"]);
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
+        std::fs::remove_file(journal_path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_data_is_not_a_version_mismatch() {
+        let items = serde_json::from_str::<ItemData>(r#"{"1":[{"name":"Sword"}]}"#).unwrap();
+        let mut plain = Vec::new();
         items
-            .versioned_serialize(&mut out, GameVersion::Ep6)
+            .versioned_serialize(&mut plain, GameVersion::Ep5)
+            .unwrap();
+        let mut encrypted = Vec::new();
+        SData::new(plain)
+            .serialize_encrypted(&mut encrypted)
             .unwrap();
 
-        std::fs::write("res/Item.SData", &out).unwrap();
+        // The newer versions run out of data, so the file is only decoded as the right version.
+        let mut fs = InMemoryFilestore::new();
+        fs.write("item/item.sdata", &encrypted, false).unwrap();
+        let (_, version) = fs.read_type::<ItemData>("item/item.sdata").unwrap();
+        assert_eq!(version, GameVersion::Ep5);
 
-        let skills = fs
-            .read_versioned_type::<SkillData>("character/skill.sdata", GameVersion::Ep6)
+        // A damaged payload fails its checksum, which no other version can fix.
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0xFF;
+        fs.write("item/item.sdata", &encrypted, false).unwrap();
+        let err = fs.read_type::<ItemData>("item/item.sdata").unwrap_err();
+        let FilestoreError::Corrupt {
+            version,
+            ref source,
+        } = err
+        else {
+            panic!("expected corrupt data, found {err:?}");
+        };
+        assert_eq!(version, *GameVersion::last());
+        assert_eq!(source.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn open_pair_from_base_name() {
+        let (header_path, data_path) = temp_paths("pair");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", b"filter", true).unwrap();
+        drop(fs);
+
+        let mut fs = ImmutableFilestore::open_pair(header_path.with_extension("")).unwrap();
+        assert_eq!(fs.read("filter.txt").unwrap(), b"filter");
+
+        let data_path = data_path.to_string_lossy().into_owned();
+        assert!(ImmutableFilestore::open(header_path.as_path(), data_path).is_ok());
+    }
+
+    #[test]
+    fn detect_version_from_structure() {
+        let mut fs = InMemoryFilestore::new();
+        for version in [GameVersion::Ep5, GameVersion::Ep6, GameVersion::Ep6v2] {
+            let records = (1..=3)
+                .map(|id| ItemRecord {
+                    name: format!("Item {id}"),
+                    item_type: 2,
+                    item_type_id: id,
+                    ..Default::default()
+                })
+                .collect();
+            let mut plain = Vec::new();
+            ItemData::from(BTreeMap::from([(2, records)]))
+                .versioned_serialize(&mut plain, version)
+                .unwrap();
+            let mut encrypted = Vec::new();
+            SData::new(plain)
+                .serialize_encrypted(&mut encrypted)
+                .unwrap();
+
+            fs.write("item/item.sdata", &encrypted, false).unwrap();
+            assert_eq!(
+                fs.detect_version::<ItemData>("item/item.sdata"),
+                Some(version)
+            );
+        }
+
+        for version in [GameVersion::Ep4, GameVersion::Ep5] {
+            let records = (1..=5)
+                .map(|rank| SkillRecord {
+                    name: "Slash".to_owned(),
+                    rank,
+                    ..Default::default()
+                })
+                .collect();
+            let mut plain = Vec::new();
+            SkillData::from(BTreeMap::from([(1, records)]))
+                .versioned_serialize(&mut plain, version)
+                .unwrap();
+
+            fs.write("character/skill.sdata", &plain, false).unwrap();
+            assert_eq!(
+                fs.detect_version::<SkillData>("character/skill.sdata"),
+                Some(version)
+            );
+            let (_, read_version) = fs.read_type::<SkillData>("character/skill.sdata").unwrap();
+            assert_eq!(read_version, version);
+        }
+
+        fs.write("filter.txt", b"not a table", false).unwrap();
+        assert_eq!(fs.detect_version::<ItemData>("filter.txt"), None);
+        assert_eq!(fs.detect_version::<ItemData>("missing.sdata"), None);
+
+        // A table of nothing but placeholder records fits every layout equally well.
+        let placeholders = (1..=20).map(|_| ItemRecord::default()).collect();
+        let mut plain = Vec::new();
+        ItemData::from(BTreeMap::from([(1, placeholders)]))
+            .versioned_serialize(&mut plain, GameVersion::Ep6)
+            .unwrap();
+        let mut encrypted = Vec::new();
+        SData::new(plain)
+            .serialize_encrypted(&mut encrypted)
             .unwrap();
+        fs.write("item/item.sdata", &encrypted, false).unwrap();
+        assert_eq!(fs.detect_version::<ItemData>("item/item.sdata"), None);
+    }
 
-        std::fs::write(
-            "res/skills.json",
-            serde_json::to_vec_pretty(&skills).unwrap(),
-        )
-        .unwrap();
-        let mut out = Vec::new();
-        skills
-            .versioned_serialize(&mut out, GameVersion::Ep6)
+    #[test]
+    fn node_encryption_status() {
+        let mut plain = Vec::new();
+        ItemData::default()
+            .versioned_serialize(&mut plain, GameVersion::Ep5)
+            .unwrap();
+        let mut encrypted = Vec::new();
+        SData::new(plain)
+            .serialize_encrypted(&mut encrypted)
             .unwrap();
 
-        std::fs::write("res/Skill.SData", &out).unwrap();
+        let mut fs = InMemoryFilestore::new();
+        fs.write("item/item.sdata", &encrypted, false).unwrap();
+        fs.write("filter.txt", b"plain text", false).unwrap();
+        assert_eq!(fs.is_node_encrypted("item/item.sdata"), Some(true));
+        assert_eq!(fs.is_node_encrypted("filter.txt"), Some(false));
+        assert_eq!(fs.is_node_encrypted("missing.sdata"), None);
+
+        let (header_path, data_path) = temp_paths("encryption_status");
+        let mut mutable = MutableFilestore::create(&header_path, &data_path).unwrap();
+        mutable.write("item/item.sdata", &encrypted, false).unwrap();
+        mutable.write("filter.txt", b"plain text", false).unwrap();
+        assert_eq!(mutable.read_prefix("filter.txt", 5).unwrap(), b"plain");
+        assert_eq!(mutable.is_node_encrypted("item/item.sdata"), Some(true));
+        assert_eq!(mutable.is_node_encrypted("filter.txt"), Some(false));
     }
 
     #[test]
-    fn sdata_read() {
-        let indata = std::fs::read("res/Skill.SData").unwrap();
-        let mut src = Cursor::new(indata.as_slice());
-        let sdata = SkillData::versioned_deserialize(&mut src, GameVersion::Ep6).unwrap();
+    #[cfg(feature = "metrics")]
+    fn read_metrics_add_up() {
+        let (header_path, data_path) = temp_paths("metrics");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", &[1; 10], false).unwrap();
+        fs.write("item/item.sdata", &[2; 100], true).unwrap();
 
-        std::fs::write(
-            "res/skillsout.json",
-            serde_json::to_vec_pretty(&sdata).unwrap(),
-        )
-        .unwrap();
+        fs.read("filter.txt").unwrap();
+        fs.read("item/item.sdata").unwrap();
+        fs.read_prefix("item/item.sdata", 40).unwrap();
+        assert!(fs.read("missing.txt").is_none());
+
+        let metrics = fs.metrics();
+        assert_eq!(metrics.nodes_read, 3);
+        assert_eq!(metrics.bytes_read, 150);
+
+        let mut fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        for _ in 0..3 {
+            fs.read("item/item.sdata").unwrap();
+        }
+        assert!(fs.read("missing.txt").is_none());
+        assert_eq!(fs.metrics().nodes_read, 3);
+        assert_eq!(fs.metrics().bytes_read, 300);
     }
 
     #[test]
-    fn item_test() {
-        let item = std::fs::read("res/KreonItem.SData").unwrap();
-        let mut src = Cursor::new(item.as_slice());
+    fn load_all_matches_read() {
+        let (header_path, data_path) = temp_paths("load-all");
+        let mut writer = MutableFilestore::create(&header_path, &data_path).unwrap();
+        writer.write("filter.txt", b"filter", false).unwrap();
+        writer.write("item/item.sdata", &[3; 256], false).unwrap();
+        writer.write("data/world/2.wld", &[], true).unwrap();
 
-        let items = ItemData::versioned_deserialize(&mut src, GameVersion::Ep6v2).unwrap();
-        std::fs::write(
-            "res/kreonitems.json",
-            serde_json::to_vec_pretty(&items).unwrap(),
-        )
-        .unwrap();
+        let mut fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        let loaded = fs.load_all();
+        assert_eq!(loaded.len(), fs.all_node_paths().len());
+        for (path, data) in &loaded {
+            assert_eq!(fs.read(path).as_ref(), Some(data));
+        }
     }
 
     #[test]
-    fn patch_test() {
-        let mut other = ImmutableFilestore::open("res/data.sah", "res/data.saf").unwrap();
-        let mut fs =
-            MutableFilestore::create("res/new_with_crc.sah", "res/new_with_crc.saf").unwrap();
+    fn mapped_reads_follow_writes() {
+        let (header_path, data_path) = temp_paths("mapped-reads");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.enable_mapped_reads(true);
+        fs.write("filter.txt", b"filter", false).unwrap();
+        fs.write("item/item.sdata", &[3; 256], false).unwrap();
+
+        for _ in 0..1000 {
+            assert_eq!(fs.read("item/item.sdata").unwrap(), [3; 256]);
+        }
+
+        // Overwrite in place, and then grow the data file past the end of the existing map.
+        fs.write("item/item.sdata", &[4; 128], false).unwrap();
+        assert_eq!(fs.read("item/item.sdata").unwrap(), [4; 128]);
+        fs.write("character/skill.sdata", &[5; 4096], false)
+            .unwrap();
+        fs.write_type_streaming("filter.txt", &String::from("changed"), GameVersion::Ep5)
+            .unwrap();
+
+        for _ in 0..1000 {
+            assert_eq!(fs.read("character/skill.sdata").unwrap(), [5; 4096]);
+            assert_eq!(fs.read("item/item.sdata").unwrap(), [4; 128]);
+        }
+        assert_eq!(fs.read_type::<String>("filter.txt").unwrap().0, "changed");
+        assert_eq!(
+            fs.read_prefix("character/skill.sdata", 10).unwrap(),
+            [5; 10]
+        );
+
+        fs.enable_mapped_reads(false);
+        assert_eq!(fs.read("character/skill.sdata").unwrap(), [5; 4096]);
+    }
+
+    #[test]
+    fn patch_dry_run_matches_patch() {
+        let (header_path, data_path) = temp_paths("dry-run");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", &[1; 64], false).unwrap();
+        fs.write("item/item.sdata", &[2; 128], false).unwrap();
+        fs.write("character/skill.sdata", &[3; 32], true).unwrap();
+
+        let mut other = InMemoryFilestore::new();
+        other.write("filter.txt", &[4; 16], false).unwrap();
+        other.write("item/item.sdata", &[5; 512], false).unwrap();
+        other
+            .write("character/skill.sdata", &[6; 48], false)
+            .unwrap();
+        other.write("data/world/2.wld", &[7; 96], false).unwrap();
+
+        let before = std::fs::metadata(&data_path).unwrap().len() as usize;
+        let offsets = |fs: &MutableFilestore| {
+            fs.entries()
+                .map(|(path, info)| (path, info.offset))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let old_offsets = offsets(&fs);
+
+        let plan = fs.patch_dry_run(&mut other).unwrap();
+        assert_eq!(
+            std::fs::metadata(&data_path).unwrap().len() as usize,
+            before
+        );
+        assert_eq!(offsets(&fs), old_offsets);
+        assert_eq!(plan.created, ["data/world/2.wld"]);
+        assert_eq!(plan.overwritten, ["filter.txt"]);
+        assert_eq!(plan.grown.len(), 2);
+
         fs.patch(&mut other).unwrap();
+        let after = std::fs::metadata(&data_path).unwrap().len() as usize;
+        assert_eq!(after - before, plan.bytes_added);
+
+        let new_offsets = offsets(&fs);
+        for path in &plan.overwritten {
+            assert_eq!(new_offsets[path], old_offsets[path]);
+        }
+        for path in &plan.grown {
+            assert_ne!(new_offsets[path], old_offsets[path]);
+        }
+        for path in &plan.created {
+            assert!(!old_offsets.contains_key(path));
+            assert!(new_offsets.contains_key(path));
+        }
+    }
+
+    #[test]
+    fn create_new_refuses_existing_files() {
+        let (header_path, data_path) = temp_paths("create-new");
+        let _ = std::fs::remove_file(&header_path);
+        let _ = std::fs::remove_file(&data_path);
+
+        let mut fs = MutableFilestore::create_new(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", b"filter", true).unwrap();
+        drop(fs);
+
+        let err = MutableFilestore::create_new(&header_path, &data_path)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+
+        // Only the data file exists, so the header must not be left behind.
+        std::fs::remove_file(&header_path).unwrap();
+        let err = MutableFilestore::create_new(&header_path, &data_path)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert!(!header_path.exists());
+        assert_eq!(std::fs::read(&data_path).unwrap(), b"filter");
+    }
+
+    #[test]
+    fn patch_from_carries_checksums() {
+        // The source checksums are deliberately wrong, so they only survive the patch if they
+        // are carried over rather than computed again.
+        let (source_header, source_data) = temp_paths("patch-from-source");
+        let header = HeaderBuilder::new()
+            .nodes([("filter.txt", 6, 0x1111), ("item/item.sdata", 4, 0x2222)])
+            .build()
+            .unwrap();
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        std::fs::write(&source_header, dst).unwrap();
+        std::fs::write(&source_data, b"filteritem").unwrap();
+        let source = ImmutableFilestore::open(&source_header, &source_data).unwrap();
+
+        let (header_path, data_path) = temp_paths("patch-from");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.patch_from(&source).unwrap();
+        assert_eq!(fs.read("filter.txt").unwrap(), b"filter");
+        assert_eq!(fs.read("item/item.sdata").unwrap(), b"item");
+        for (path, info) in source.entries() {
+            assert_eq!(fs.stat(&path).unwrap().checksum, info.checksum);
+        }
+
+        // With a different algorithm, the checksums have to be computed again.
+        fs.set_checksum_algorithm(ChecksumAlgorithm::Additive);
+        fs.patch_from(&source).unwrap();
+        let expected = ChecksumAlgorithm::Additive.checksum(b"filter");
+        assert_eq!(fs.stat("filter.txt").unwrap().checksum, expected);
+    }
+
+    #[test]
+    fn in_memory_paths_are_normalized() {
+        let mut fs = InMemoryFilestore::new();
+        fs.write("item\\item.sdata", b"items", false).unwrap();
+        fs.write("/item/item.sdata", b"newer", false).unwrap();
+        fs.write("character\\skill.sdata", b"skills", false)
+            .unwrap();
+
+        assert_eq!(
+            fs.all_node_paths(),
+            ["character/skill.sdata", "item/item.sdata"]
+        );
+        assert_eq!(fs.read("item/item.sdata").unwrap(), b"newer");
+        assert_eq!(fs.stat("\\character\\skill.sdata").unwrap().length, 6);
+
+        let (header_path, data_path) = temp_paths("in-memory-normalized");
+        let mut disk = MutableFilestore::create(&header_path, &data_path).unwrap();
+        disk.patch(&mut fs).unwrap();
+        let mut patched = InMemoryFilestore::new();
+        patched.patch(&mut disk).unwrap();
+        assert_eq!(patched.all_node_paths(), fs.all_node_paths());
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
+    }
+
+    #[test]
+    fn remove_accepts_native_paths() {
+        let (header_path, data_path) = temp_paths("remove-native-paths");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("item/item.sdata", b"items", false).unwrap();
+        fs.write("character/skill.sdata", b"skills", true).unwrap();
+
+        fs.remove("item\\item.sdata", false).unwrap();
+        fs.remove("/character/skill.sdata", true).unwrap();
+        assert!(fs.all_node_paths().is_empty());
+        assert!(matches!(
+            fs.remove("item\\item.sdata", false),
+            Err(FilestoreError::NodeNotFound(_))
+        ));
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
+    }
+
+    #[test]
+    fn write_reports_outcome() {
+        let (header_path, data_path) = temp_paths("write-outcome");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+
+        let outcome = fs.write("filter.txt", b"filter", false).unwrap();
+        assert!(!outcome.reused_slot);
+        assert_eq!(outcome.bytes_appended, 6);
+
+        let outcome = fs.write("filter.txt", b"filt", false).unwrap();
+        assert!(outcome.reused_slot);
+        assert_eq!(outcome.bytes_appended, 0);
+
+        let outcome = fs.write("filter.txt", b"filter", false).unwrap();
+        assert!(outcome.reused_slot);
+        assert_eq!(outcome.bytes_appended, 0);
+
+        let outcome = fs.write("filter.txt", b"filter.txt", true).unwrap();
+        assert!(!outcome.reused_slot);
+        assert_eq!(outcome.bytes_appended, 10);
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
+    }
+
+    #[test]
+    fn large_nodes_are_verified_in_chunks() {
+        let data = (0..3 * 1024 * 1024 + 7)
+            .map(|i| (i * 31 % 251) as u8)
+            .collect::<Vec<_>>();
+        for algorithm in [ChecksumAlgorithm::Crc32, ChecksumAlgorithm::Additive] {
+            let streamed = algorithm
+                .checksum_reader(&mut Cursor::new(&data), data.len())
+                .unwrap();
+            assert_eq!(streamed, algorithm.checksum(&data));
+        }
+
+        let (header_path, data_path) = temp_paths("chunked-verify");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("model/large.3dc", &data, false).unwrap();
+        fs.write("filter.txt", b"filter", true).unwrap();
+        assert!(fs.corrupt_nodes().is_empty());
+
+        fs.enable_mapped_reads(true);
+        assert!(fs.corrupt_nodes().is_empty());
+        fs.enable_mapped_reads(false);
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&data_path)
+            .unwrap();
+        file.seek(SeekFrom::Start(2 * 1024 * 1024)).unwrap();
+        file.write_all(&[!data[2 * 1024 * 1024]]).unwrap();
+        drop(file);
+        assert_eq!(fs.corrupt_nodes(), ["model/large.3dc"]);
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
+    }
+
+    #[test]
+    fn appended_nodes_are_aligned() {
+        let (header_path, data_path) = temp_paths("aligned-writes");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.set_alignment(512);
+
+        let outcome = fs.write("filter.txt", b"filter", false).unwrap();
+        assert_eq!(outcome.bytes_appended, 6);
+        let outcome = fs.write("item/item.sdata", &[1; 700], false).unwrap();
+        assert_eq!(outcome.bytes_appended, 506 + 700);
+        fs.write("character/skill.sdata", &[2; 3], false).unwrap();
+
+        // Growing the first node moves it, and its old aligned space is reused by a smaller node.
+        fs.write("filter.txt", &[3; 40], false).unwrap();
+        fs.write("monster.sdata", &[4; 5], true).unwrap();
+
+        let entries = fs.entries().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 4);
+        for (path, info) in &entries {
+            assert_eq!(info.offset % 512, 0, "{path} is not aligned");
+        }
+        assert_eq!(fs.stat("monster.sdata").unwrap().offset, 0);
+        assert!(fs.corrupt_nodes().is_empty());
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
+    }
+
+    #[test]
+    fn verified_reads_report_mismatches() {
+        let (header_path, data_path) = temp_paths("verified-read");
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", b"filter", false).unwrap();
+        fs.write("item/item.sdata", b"items", true).unwrap();
+        assert_eq!(fs.read_verified("filter.txt").unwrap(), b"filter");
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&data_path)
+            .unwrap();
+        file.write_all(b"F").unwrap();
+        drop(file);
+
+        let expected = ChecksumAlgorithm::Crc32.checksum(b"filter");
+        let actual = ChecksumAlgorithm::Crc32.checksum(b"Filter");
+        for err in [
+            fs.read_verified("filter.txt").err().unwrap(),
+            ImmutableFilestore::open(&header_path, &data_path)
+                .unwrap()
+                .read_verified("filter.txt")
+                .err()
+                .unwrap(),
+        ] {
+            assert!(matches!(
+                err,
+                FilestoreError::ChecksumMismatch { path, expected: e, actual: a }
+                    if path == "filter.txt" && e == expected && a == actual
+            ));
+        }
+        assert!(matches!(
+            fs.read_verified("missing.txt"),
+            Err(FilestoreError::NodeNotFound(_))
+        ));
+        assert_eq!(fs.read_verified("item/item.sdata").unwrap(), b"items");
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_offsets_near_the_maximum() {
+        let (header_path, data_path) = temp_paths("verify-max-offset");
+        let mut header = HeaderBuilder::new()
+            .node(
+                "filter.txt",
+                6,
+                ChecksumAlgorithm::Crc32.checksum(b"filter"),
+            )
+            .build()
+            .unwrap();
+        let inode = crate::fs::header::Inode {
+            name: "corrupt.txt".to_owned(),
+            offset: u64::MAX as usize - 2,
+            length: 10,
+            checksum: 0,
+        };
+        header.emplace_node("corrupt.txt", inode).unwrap();
+
+        let mut dst = Vec::new();
+        header.serialize(&mut dst).unwrap();
+        std::fs::write(&header_path, dst).unwrap();
+        std::fs::write(&data_path, b"filter").unwrap();
+
+        let fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        assert_eq!(fs.verify(), ["corrupt.txt"]);
+        assert_eq!(fs.verify_parallel(), ["corrupt.txt"]);
+
+        std::fs::remove_file(header_path).unwrap();
+        std::fs::remove_file(data_path).unwrap();
     }
 }
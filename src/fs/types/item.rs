@@ -1,11 +1,25 @@
 use crate::fs::types::*;
-use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt};
+use crate::io::{
+    Deserialize, GameVersion, Serialize, SerializedLen, ShaiyaReadExt, ShaiyaWriteExt,
+};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use std::collections::BTreeMap;
 use std::io::{Cursor, Read, Write};
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-pub struct ItemData(BTreeMap<usize, Vec<ItemRecord>>);
+/// Every item record, grouped by item type. An empty set of items is written as a zero item type
+/// count, with no records following it. The [GameVersion] the items were decoded with is retained,
+/// but is not included when serializing with serde.
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct ItemData {
+    records: BTreeMap<usize, Vec<ItemRecord>>,
+    #[serde(skip)]
+    decoded_version: Option<GameVersion>,
+    #[serde(skip)]
+    original: Option<Vec<u8>>,
+    #[serde(skip)]
+    dirty: bool,
+}
 
 sdata_record!(ItemRecord {
     name                String;
@@ -16,12 +30,7 @@ sdata_record!(ItemRecord {
     icon                u8;
     min_level           u16;
     permitted_races     PermittedRace;
-    usable_by_fighter   bool;
-    usable_by_defender  bool;
-    usable_by_ranger    bool;
-    usable_by_archer    bool;
-    usable_by_mage      bool;
-    usable_by_priest    bool;
+    usable_by           ClassMask;
     min_game_mode       GameMode;
     type2               u8;
     type3               u8;
@@ -84,31 +93,374 @@ sdata_record!(ItemRecord {
     arg14               u32     if(ep6_or_above);
 });
 
-impl Deserialize for ItemData {
-    type Error = std::io::Error;
+/// The highest stat requirement an item is expected to have when validating with
+/// [ItemData::validate]. Anything above this is almost certainly a typo in an item editor.
+pub const DEFAULT_MAX_REQUIREMENT: u16 = 1000;
 
-    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
+/// A problem with an item record which doesn't stop it from being serialized, but is likely to be
+/// a mistake. Items are identified by their item type, and their 1-based index within that type.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ItemValidationWarning {
+    /// None of the `usable_by` flags are set, so no class can equip the item.
+    NotUsableByAnyClass {
+        item_type: usize,
+        item_type_id: usize,
+    },
+
+    /// A stat requirement is higher than the configured maximum.
+    RequirementTooHigh {
+        item_type: usize,
+        item_type_id: usize,
+        stat: &'static str,
+        value: u16,
+    },
+
+    /// The item sells to an NPC for more than it can be bought for.
+    SellPriceExceedsBuyPrice {
+        item_type: usize,
+        item_type_id: usize,
+        buy_price: u32,
+        sell_price: u32,
+    },
+}
+
+impl ItemData {
+    /// Gets the [GameVersion] these items were decoded with, or migrated to with [Self::migrate].
+    /// This will return `None` if the items were not decoded from an `.SData` file.
+    pub fn version(&self) -> Option<GameVersion> {
+        self.decoded_version
+    }
+
+    /// Gets the records, grouped by item type.
+    pub fn records(&self) -> &BTreeMap<usize, Vec<ItemRecord>> {
+        &self.records
+    }
+
+    /// Gets mutable access to the records, grouped by item type. This marks the items as modified, so
+    /// they will be re-encoded when serialized rather than written back verbatim.
+    pub fn records_mut(&mut self) -> &mut BTreeMap<usize, Vec<ItemRecord>> {
+        self.dirty = true;
+        &mut self.records
+    }
+
+    /// Iterates over every record, ordered by item type and id, without cloning them.
+    pub fn values(&self) -> impl Iterator<Item = &ItemRecord> {
+        self.records.values().flatten()
+    }
+
+    /// Iterates over every record, ordered by item type and id, allowing them to be edited in place. This
+    /// marks the items as modified, like [Self::records_mut].
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut ItemRecord> {
+        self.records_mut().values_mut().flatten()
+    }
+
+    /// Checks if the items have been modified since they were decoded. ItemData which wasn't decoded
+    /// from an `.SData` file is always considered modified.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.original.is_none()
+    }
+
+    /// Gets the bytes these items were decoded from, if they haven't been modified since and are
+    /// being encoded for the same [GameVersion]. Unmodified items are written back exactly as they
+    /// were read, which avoids re-encoding them and keeps any encryption intact.
+    ///
+    /// # Arguments
+    /// * `version` - The game version being encoded for.
+    fn unmodified_bytes(&self, version: GameVersion) -> Option<&[u8]> {
+        match &self.original {
+            Some(original) if !self.dirty && self.decoded_version == Some(version) => {
+                Some(original)
+            }
+            _ => None,
+        }
+    }
+
+    /// Merges another set of items over these ones. Each record in `overlay` replaces the record
+    /// with the same item type and id, and any records which don't exist yet are added. Records
+    /// without a name in `overlay` are treated as unused placeholders, and don't replace anything.
+    ///
+    /// # Arguments
+    /// * `overlay` - The items to merge over these ones.
+    pub fn merge(&mut self, overlay: ItemData) {
+        merge_records(self.records_mut(), overlay.records, |record| {
+            record.name.is_empty()
+        });
+    }
+
+    /// Converts these items from the record layout of one [GameVersion] to another. Fields which
+    /// only exist in `to` are left at their defaults, and fields which don't exist in `to` are
+    /// dropped. Every record is re-encoded with the layout of `to`, so a value which can't be
+    /// represented in that version is reported as an error rather than being written incorrectly.
+    ///
+    /// # Arguments
+    /// * `from`    - The version the items are currently laid out for.
+    /// * `to`      - The version to migrate the items to.
+    pub fn migrate(&self, from: GameVersion, to: GameVersion) -> Result<ItemData, std::io::Error> {
+        if let Some(decoded) = self.decoded_version.filter(|decoded| *decoded != from) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("items were decoded as {decoded}, not {from}"),
+            ));
+        }
+
+        let mut records = BTreeMap::new();
+        let mut buf = Vec::new();
+        for (&item_type, items) in &self.records {
+            let mut migrated = Vec::with_capacity(items.len());
+            for (index, record) in items.iter().enumerate() {
+                buf.clear();
+                let record = record
+                    .versioned_serialize(&mut buf, to)
+                    .and_then(|_| ItemRecord::versioned_deserialize(&mut buf.as_slice(), to))
+                    .map_err(|err| {
+                        std::io::Error::new(
+                            err.kind(),
+                            format!(
+                                "failed migrating item type {item_type} id {} to {to}: {err}",
+                                index + 1
+                            ),
+                        )
+                    })?;
+                migrated.push(record);
+            }
+            records.insert(item_type, migrated);
+        }
+
+        Ok(Self {
+            records,
+            decoded_version: Some(to),
+            ..Default::default()
+        })
+    }
+
+    /// Checks every item for records which are likely to be mistakes, using
+    /// [DEFAULT_MAX_REQUIREMENT] as the highest sane stat requirement. Records without a name are
+    /// treated as unused placeholders, and skipped.
+    pub fn validate(&self) -> Vec<ItemValidationWarning> {
+        self.validate_with_max_requirement(DEFAULT_MAX_REQUIREMENT)
+    }
+
+    /// Checks every item for records which are likely to be mistakes. Records without a name are
+    /// treated as unused placeholders, and skipped.
+    ///
+    /// # Arguments
+    /// * `max_requirement` - The highest stat requirement an item may have.
+    pub fn validate_with_max_requirement(
+        &self,
+        max_requirement: u16,
+    ) -> Vec<ItemValidationWarning> {
+        let mut warnings = Vec::new();
+        for (&item_type, records) in &self.records {
+            for (index, record) in records.iter().enumerate() {
+                if record.name.is_empty() {
+                    continue;
+                }
+
+                let item_type_id = index + 1;
+                if record.usable_by.is_empty() {
+                    warnings.push(ItemValidationWarning::NotUsableByAnyClass {
+                        item_type,
+                        item_type_id,
+                    });
+                }
+
+                let requirements = [
+                    ("str", record.req_str),
+                    ("dex", record.req_dex),
+                    ("rec", record.req_rec),
+                    ("int", record.req_int),
+                    ("wis", record.req_wis),
+                    ("luc", record.req_luc),
+                ];
+                for (stat, value) in requirements {
+                    if value > max_requirement {
+                        warnings.push(ItemValidationWarning::RequirementTooHigh {
+                            item_type,
+                            item_type_id,
+                            stat,
+                            value,
+                        });
+                    }
+                }
+
+                if record.buy_price < record.sell_price {
+                    warnings.push(ItemValidationWarning::SellPriceExceedsBuyPrice {
+                        item_type,
+                        item_type_id,
+                        buy_price: record.buy_price,
+                        sell_price: record.sell_price,
+                    });
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Reads the items from an `.SData` file which was encrypted with a given SEED key. This is
+    /// required for clients which don't use [SHAIYA_SEED_KEY].
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `version` - The game version.
+    /// * `key`     - The SEED key.
+    pub fn deserialize_with_key<T>(
+        src: &mut T,
+        version: GameVersion,
+        key: &[u32; 32],
+    ) -> Result<Self, std::io::Error>
     where
         T: Read + ReadBytesExt,
-        Self: Sized,
     {
-        let mut decrypted = SData::deserialize(src)?;
-        let mut src = Cursor::new(&mut decrypted.data);
+        Self::deserialize_sdata(src, version, key, false)
+    }
+
+    /// Reads the items from an `.SData` file like [Self::deserialize_with_key], but fails with
+    /// [TrailingBytes](crate::io::TrailingBytes) if the records don't take up the whole decrypted
+    /// payload. This catches a record layout which doesn't match the file, rather than silently
+    /// reading the wrong fields.
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `version` - The game version.
+    /// * `key`     - The SEED key.
+    pub fn deserialize_strict<T>(
+        src: &mut T,
+        version: GameVersion,
+        key: &[u32; 32],
+    ) -> Result<Self, std::io::Error>
+    where
+        T: Read + ReadBytesExt,
+    {
+        Self::deserialize_sdata(src, version, key, true)
+    }
+}
 
+impl SdataBacked for ItemData {
+    fn from_decrypted(
+        src: &mut Cursor<&[u8]>,
+        version: GameVersion,
+    ) -> Result<Self, std::io::Error> {
         let mut map = BTreeMap::new();
         let max_item_type = src.read_u32::<LE>()? as usize;
         for item_type in 1..=max_item_type {
             let max_item_type_id = src.read_u32::<LE>()? as usize;
-            for _item_type_id in 1..=max_item_type_id {
-                let record = ItemRecord::versioned_deserialize(&mut src, version)?;
+            for item_type_id in 1..=max_item_type_id {
+                let record = ItemRecord::versioned_deserialize(src, version).map_err(|err| {
+                    std::io::Error::new(
+                        err.kind(),
+                        format!("failed parsing item type {item_type} id {item_type_id}: {err}"),
+                    )
+                })?;
                 map.entry(item_type)
                     .or_insert_with(|| Vec::with_capacity(max_item_type_id))
                     .push(record);
             }
         }
 
-        Ok(Self(map))
+        Ok(Self {
+            records: map,
+            decoded_version: Some(version),
+            original: None,
+            dirty: false,
+        })
+    }
+
+    fn retain_original(&mut self, original: Vec<u8>) {
+        self.original = Some(original);
+    }
+}
+
+impl SerializedLen for ItemData {
+    fn serialized_len(&self, version: GameVersion) -> usize {
+        if let Some(original) = self.unmodified_bytes(version) {
+            return original.len();
+        }
+
+        // Every item type up to the max has a count, even if it has no records.
+        let max_item_type = self.records.keys().max().copied().unwrap_or_default();
+        let records = self
+            .records
+            .range(1..)
+            .flat_map(|(_, records)| records)
+            .map(|record| record.serialized_len(version))
+            .sum::<usize>();
+        size_of::<u32>() * (1 + max_item_type) + records
+    }
+}
+
+impl From<BTreeMap<usize, Vec<ItemRecord>>> for ItemData {
+    fn from(records: BTreeMap<usize, Vec<ItemRecord>>) -> Self {
+        Self {
+            records,
+            ..Default::default()
+        }
+    }
+}
+
+impl Deserialize for ItemData {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        Self::deserialize_with_key(src, version, SHAIYA_SEED_KEY)
+    }
+
+    fn detect_version(src: &[u8]) -> Option<GameVersion> {
+        let decrypted = SData::deserialize(&mut Cursor::new(src)).ok()?;
+        GameVersion::iter().find(|version| sample_lines_up(&decrypted.data, *version))
+    }
+}
+
+/// The number of records decoded from the start of an item table when detecting its version.
+const DETECTION_SAMPLE_SIZE: usize = 16;
+
+/// Checks if the first records of a decrypted item table decode cleanly with the layout of a given
+/// version. Every item with a name carries its own item type and id, which a record layout that
+/// doesn't match the file is very unlikely to reproduce. Placeholder records without a name prove
+/// nothing, so at least one named record must be sampled for the version to be accepted.
+///
+/// # Arguments
+/// * `data`    - The decrypted item table.
+/// * `version` - The game version.
+fn sample_lines_up(data: &[u8], version: GameVersion) -> bool {
+    let mut src = Cursor::new(data);
+    let Ok(max_item_type) = src.read_u32::<LE>() else {
+        return false;
+    };
+
+    let mut sampled = 0;
+    let mut named = 0;
+    for item_type in 1..=max_item_type as usize {
+        let Ok(count) = src.read_u32::<LE>() else {
+            return false;
+        };
+
+        for item_type_id in 1..=count as usize {
+            if sampled == DETECTION_SAMPLE_SIZE {
+                return named > 0;
+            }
+
+            let Ok(record) = ItemRecord::versioned_deserialize(&mut src, version) else {
+                return false;
+            };
+            let lines_up =
+                record.item_type == item_type as u8 && record.item_type_id == item_type_id as u8;
+            if !record.name.is_empty() {
+                if !lines_up {
+                    return false;
+                }
+                named += 1;
+            }
+            sampled += 1;
+        }
     }
+
+    // The whole table fit within the sample, so it should have been consumed exactly.
+    named > 0 && src.position() as usize == data.len()
 }
 
 impl Serialize for ItemData {
@@ -118,12 +470,17 @@ impl Serialize for ItemData {
     where
         T: Write + WriteBytesExt,
     {
-        let max_item_type = *self.0.keys().max().unwrap();
-        dst.write_u32::<LE>(max_item_type as u32)?;
+        if let Some(original) = self.unmodified_bytes(version) {
+            return dst.write_all(original);
+        }
+
+        let max_item_type = self.records.keys().max().copied().unwrap_or_default();
+        dst.write_u32::<LE>(checked_count(max_item_type, "item type")?)?;
         for item_type in 1..=max_item_type {
-            match self.0.get(&item_type) {
+            match self.records.get(&item_type) {
                 Some(records) => {
-                    dst.write_u32::<LE>(records.len() as u32)?;
+                    let count = checked_count(records.len(), "item count")?;
+                    dst.write_u32::<LE>(count)?;
                     for record in records {
                         record.versioned_serialize(dst, version)?;
                     }
@@ -134,3 +491,429 @@ impl Serialize for ItemData {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ItemData, ItemValidationWarning};
+    use crate::fs::types::sdata::assert_sdata_roundtrip;
+    use crate::fs::types::{
+        ClassMask, ElementType, FieldValues, ItemRecord, PermittedRace, SData, SdataBacked,
+        SHAIYA_SEED_KEY,
+    };
+    use crate::io::{
+        is_version_mismatch, Deserialize, GameVersion, Serialize, SerializedLen, TrailingBytes,
+    };
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    #[test]
+    fn empty_round_trip() {
+        let mut dst = Vec::new();
+        ItemData::default()
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        assert_eq!(dst, [0, 0, 0, 0]);
+
+        let mut src = Cursor::new(dst.as_slice());
+        let items = ItemData::versioned_deserialize(&mut src, GameVersion::Ep6).unwrap();
+        assert!(items.records.is_empty());
+    }
+
+    #[test]
+    fn item_record_round_trip() {
+        let record = ItemRecord {
+            name: "Sword".to_owned(),
+            description: "A sword".to_owned(),
+            item_type: 1,
+            item_type_id: 2,
+            permitted_races: PermittedRace::AllLight,
+            usable_by: ClassMask::FIGHTER,
+            req_str: 50,
+            unknown: 7,
+            element: ElementType::Fire(2),
+            buy_price: 1000,
+            sell_price: 100,
+            duration: 3600,
+            arg14: 14,
+            ..Default::default()
+        };
+
+        for version in GameVersion::all() {
+            let mut bytes = Vec::new();
+            record.versioned_serialize(&mut bytes, *version).unwrap();
+            assert_sdata_roundtrip!(ItemRecord, *version, bytes);
+        }
+    }
+
+    #[test]
+    fn validate_flags_bad_items() {
+        let good = ItemRecord {
+            name: "Sword".to_owned(),
+            usable_by: ClassMask::FIGHTER,
+            req_str: 50,
+            buy_price: 1000,
+            sell_price: 100,
+            ..Default::default()
+        };
+        let bad = ItemRecord {
+            name: "Cursed Sword".to_owned(),
+            req_dex: 5000,
+            buy_price: 10,
+            sell_price: 100,
+            ..Default::default()
+        };
+        let placeholder = ItemRecord::default();
+
+        let items = ItemData::from(BTreeMap::from([(2, vec![good, placeholder, bad])]));
+        assert_eq!(
+            items.validate(),
+            [
+                ItemValidationWarning::NotUsableByAnyClass {
+                    item_type: 2,
+                    item_type_id: 3,
+                },
+                ItemValidationWarning::RequirementTooHigh {
+                    item_type: 2,
+                    item_type_id: 3,
+                    stat: "dex",
+                    value: 5000,
+                },
+                ItemValidationWarning::SellPriceExceedsBuyPrice {
+                    item_type: 2,
+                    item_type_id: 3,
+                    buy_price: 10,
+                    sell_price: 100,
+                },
+            ]
+        );
+
+        let warnings = items.validate_with_max_requirement(10);
+        assert!(
+            warnings.contains(&ItemValidationWarning::RequirementTooHigh {
+                item_type: 2,
+                item_type_id: 1,
+                stat: "str",
+                value: 50,
+            })
+        );
+    }
+
+    #[test]
+    fn truncated_record_is_named() {
+        let records = (1..=3)
+            .map(|id| ItemRecord {
+                name: format!("Item {id}"),
+                ..Default::default()
+            })
+            .collect();
+        let items = ItemData::from(BTreeMap::from([(7, records)]));
+
+        let mut dst = Vec::new();
+        items
+            .versioned_serialize(&mut dst, GameVersion::Ep5)
+            .unwrap();
+        let mut record = Vec::new();
+        items.records[&7][2]
+            .versioned_serialize(&mut record, GameVersion::Ep5)
+            .unwrap();
+        dst.truncate(dst.len() - record.len() / 2);
+
+        let mut src = Cursor::new(dst.as_slice());
+        let err = ItemData::versioned_deserialize(&mut src, GameVersion::Ep5).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(err
+            .to_string()
+            .starts_with("failed parsing item type 7 id 3: "));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn oversized_id_is_rejected() {
+        let map = BTreeMap::from([(u32::MAX as usize + 1, vec![ItemRecord::default()])]);
+
+        let mut dst = Vec::new();
+        let err = ItemData::from(map)
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn unmodified_items_are_written_verbatim() {
+        let items = ItemData::from(BTreeMap::from([(
+            1,
+            vec![ItemRecord {
+                name: "Sword".to_owned(),
+                ..Default::default()
+            }],
+        )]));
+
+        let mut plain = Vec::new();
+        items
+            .versioned_serialize(&mut plain, GameVersion::Ep5)
+            .unwrap();
+        let mut encrypted = Vec::new();
+        SData::new(plain.clone())
+            .serialize_encrypted(&mut encrypted)
+            .unwrap();
+
+        let mut src = Cursor::new(encrypted.as_slice());
+        let mut items = ItemData::versioned_deserialize(&mut src, GameVersion::Ep5).unwrap();
+        assert!(!items.is_dirty());
+
+        let mut dst = Vec::new();
+        items
+            .versioned_serialize(&mut dst, GameVersion::Ep5)
+            .unwrap();
+        assert_eq!(dst, encrypted);
+
+        let mut dst = Vec::new();
+        items
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        assert_ne!(dst, encrypted);
+
+        items.records_mut().get_mut(&1).unwrap()[0].name = "Axe".to_owned();
+        assert!(items.is_dirty());
+
+        let mut dst = Vec::new();
+        items
+            .versioned_serialize(&mut dst, GameVersion::Ep5)
+            .unwrap();
+        assert_ne!(dst, encrypted);
+
+        let mut src = Cursor::new(dst.as_slice());
+        let items = ItemData::versioned_deserialize(&mut src, GameVersion::Ep5).unwrap();
+        assert_eq!(items.records[&1][0].name, "Axe");
+    }
+
+    #[test]
+    fn merge_replaces_and_adds_items() {
+        let item = |name: &str| ItemRecord {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+
+        let mut base = ItemData::from(BTreeMap::from([(1, vec![item("Sword"), item("Axe")])]));
+        let overlay = ItemData::from(BTreeMap::from([
+            (
+                1,
+                vec![ItemRecord::default(), item("Great Axe"), item("Mace")],
+            ),
+            (3, vec![item("Bow")]),
+        ]));
+        base.merge(overlay);
+
+        let names = |records: &[ItemRecord]| {
+            records
+                .iter()
+                .map(|record| record.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&base.records()[&1]), ["Sword", "Great Axe", "Mace"]);
+        assert_eq!(names(&base.records()[&3]), ["Bow"]);
+        assert!(base.is_dirty());
+    }
+
+    #[test]
+    fn serialized_len_matches_output() {
+        let item = |name: &str, description: &str| ItemRecord {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            ..Default::default()
+        };
+        let items = ItemData::from(BTreeMap::from([
+            (1, vec![item("Sword", "A sword"), item("Axe", "")]),
+            (4, vec![item("Bow", "A long bow")]),
+        ]));
+
+        for version in GameVersion::iter() {
+            let mut dst = Vec::new();
+            items.versioned_serialize(&mut dst, version).unwrap();
+            assert_eq!(items.serialized_len(version), dst.len(), "{version}");
+        }
+
+        let mut plain = Vec::new();
+        items
+            .versioned_serialize(&mut plain, GameVersion::Ep5)
+            .unwrap();
+        let mut encrypted = Vec::new();
+        SData::new(plain)
+            .serialize_encrypted(&mut encrypted)
+            .unwrap();
+
+        let mut src = Cursor::new(encrypted.as_slice());
+        let items = ItemData::versioned_deserialize(&mut src, GameVersion::Ep5).unwrap();
+        assert_eq!(items.serialized_len(GameVersion::Ep5), encrypted.len());
+    }
+
+    #[test]
+    fn strict_read_catches_under_read() {
+        let items = ItemData::from(BTreeMap::from([(
+            1,
+            vec![ItemRecord {
+                name: "Sword".to_owned(),
+                ..Default::default()
+            }],
+        )]));
+        let mut dst = Vec::new();
+        items
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+
+        // The Ep5 layout is shorter, so reading Ep6 records with it leaves bytes unread.
+        let mut src = Cursor::new(dst.as_slice());
+        assert!(ItemData::versioned_deserialize(&mut src, GameVersion::Ep5).is_ok());
+
+        let mut src = Cursor::new(dst.as_slice());
+        let err =
+            ItemData::deserialize_strict(&mut src, GameVersion::Ep5, SHAIYA_SEED_KEY).unwrap_err();
+        assert!(is_version_mismatch(&err));
+        assert!(err.get_ref().unwrap().is::<TrailingBytes>());
+
+        let mut src = Cursor::new(dst.as_slice());
+        let decoded =
+            ItemData::deserialize_strict(&mut src, GameVersion::Ep6, SHAIYA_SEED_KEY).unwrap();
+        assert_eq!(decoded.records, items.records);
+    }
+
+    #[test]
+    fn migrate_ep5_to_ep6() {
+        let items = ItemData::from(BTreeMap::from([(
+            3,
+            vec![ItemRecord {
+                name: "Bow".to_owned(),
+                buy_price: 100,
+                ..Default::default()
+            }],
+        )]));
+        let mut dst = Vec::new();
+        items
+            .versioned_serialize(&mut dst, GameVersion::Ep5)
+            .unwrap();
+        let mut src = Cursor::new(dst.as_slice());
+        let items = ItemData::versioned_deserialize(&mut src, GameVersion::Ep5).unwrap();
+
+        assert!(items.migrate(GameVersion::Ep4, GameVersion::Ep6).is_err());
+        let migrated = items.migrate(GameVersion::Ep5, GameVersion::Ep6).unwrap();
+        assert_eq!(migrated.version(), Some(GameVersion::Ep6));
+
+        let mut dst = Vec::new();
+        migrated
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        let mut src = Cursor::new(dst.as_slice());
+        let decoded =
+            ItemData::deserialize_strict(&mut src, GameVersion::Ep6, SHAIYA_SEED_KEY).unwrap();
+
+        let record = &decoded.records[&3][0];
+        assert_eq!(record.name, "Bow");
+        assert_eq!(record.buy_price, 100);
+        assert_eq!(record.duration, 0);
+        assert_eq!(decoded.records, migrated.records);
+    }
+
+    #[test]
+    fn field_hints_cover_every_field() {
+        // Each field of the pretty-printed record is on its own line, with a single indent.
+        let debug = format!("{:#?}", ItemRecord::default());
+        let fields = debug
+            .lines()
+            .filter_map(|line| line.strip_prefix("    "))
+            .filter(|line| !line.starts_with(' '))
+            .filter_map(|line| line.split_once(':').map(|(name, _)| name))
+            .collect::<Vec<_>>();
+
+        let hints = ItemRecord::field_hints();
+        assert_eq!(hints.len(), fields.len());
+        assert!(hints
+            .iter()
+            .zip(&fields)
+            .all(|(hint, name)| hint.name == *name));
+
+        let hint = |name: &str| hints.iter().find(|hint| hint.name == name).unwrap();
+        assert_eq!(hint("name").values, FieldValues::Any);
+        assert_eq!(hint("min_game_mode").type_name, "GameMode");
+        assert!(matches!(
+            hint("usable_by").values,
+            FieldValues::FlagsOf(["FIGHTER", "DEFENDER", ..])
+        ));
+        assert!(matches!(
+            hint("element").values,
+            FieldValues::OneOf(["None", "Fire", ..])
+        ));
+    }
+
+    #[test]
+    fn sdata_backed_matches_deserialize() {
+        let items = ItemData::from(BTreeMap::from([(
+            1,
+            vec![ItemRecord {
+                name: "Sword".to_owned(),
+                item_type: 1,
+                item_type_id: 1,
+                ..Default::default()
+            }],
+        )]));
+        let mut plain = Vec::new();
+        items
+            .versioned_serialize(&mut plain, GameVersion::Ep6)
+            .unwrap();
+        let mut encrypted = Vec::new();
+        SData::new(plain.clone())
+            .serialize_encrypted(&mut encrypted)
+            .unwrap();
+
+        let read =
+            ItemData::versioned_deserialize(&mut encrypted.as_slice(), GameVersion::Ep6).unwrap();
+        let parsed =
+            ItemData::from_decrypted(&mut Cursor::new(plain.as_slice()), GameVersion::Ep6).unwrap();
+        assert_eq!(read.records, parsed.records);
+        assert_eq!(read.records, items.records);
+        assert!(!read.is_dirty());
+        assert!(parsed.is_dirty());
+
+        let strict = ItemData::deserialize_sdata(
+            &mut encrypted.as_slice(),
+            GameVersion::Ep6,
+            SHAIYA_SEED_KEY,
+            true,
+        )
+        .unwrap();
+        let mut written = Vec::new();
+        strict
+            .versioned_serialize(&mut written, GameVersion::Ep6)
+            .unwrap();
+        assert_eq!(written, encrypted);
+    }
+
+    #[test]
+    fn values_cover_every_record() {
+        let record = |name: &str| ItemRecord {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let mut items = ItemData::from(BTreeMap::from([
+            (1, vec![record("Sword"), record("Axe")]),
+            (2, vec![]),
+            (3, vec![record("Bow")]),
+        ]));
+
+        let total = items.records().values().map(Vec::len).sum::<usize>();
+        assert_eq!(items.values().count(), total);
+        assert_eq!(
+            items
+                .values()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Sword", "Axe", "Bow"]
+        );
+
+        for item in items.values_mut() {
+            item.buy_price = 100;
+        }
+        assert!(items.values().all(|item| item.buy_price == 100));
+        assert!(items.is_dirty());
+    }
+}
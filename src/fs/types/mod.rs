@@ -11,6 +11,11 @@ pub use skilldata::*;
 use std::io::{ErrorKind, Read, Write};
 pub use text::*;
 
+/// The minimum game mode required to use an item or skill. This is encoded as a single byte, and
+/// the ids are the same in every supported game version, from Ep4 through Ep6v2. The only
+/// reference for the table is the crate's original decoder, which read every episode with it, and
+/// no episode is known to encode it differently. The version is deliberately not taken into
+/// account, so a version which does differ needs its own table in `for_id`.
 #[derive(Default, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
 pub enum GameMode {
     #[default]
@@ -20,6 +25,11 @@ pub enum GameMode {
     Ultimate,
 }
 
+/// The races or factions permitted to use an item or skill. This is encoded as a single byte, and
+/// the ids are the same in every supported game version, from Ep4 through Ep6v2. The only
+/// reference for the table is the crate's original decoder, which read every episode with it, and
+/// no episode is known to encode it differently. The version is deliberately not taken into
+/// account, so a version which does differ needs its own table in `for_id`.
 #[derive(Default, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
 pub enum PermittedRace {
     Human,
@@ -43,25 +53,290 @@ pub enum ElementType {
     Wind(u8),
 }
 
+/// The values a field of a record may hold. This lets tools such as editors pick a suitable
+/// widget for each field, without hard-coding every record type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FieldValues {
+    /// Any value of the field's type.
+    Any,
+    /// Exactly one of a set of named values.
+    OneOf(&'static [&'static str]),
+    /// Any combination of a set of named flags.
+    FlagsOf(&'static [&'static str]),
+}
+
+/// Describes a single field of a record, in the order the fields are encoded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FieldHint {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub values: FieldValues,
+}
+
+/// A field type which can only hold certain named values.
+pub trait NamedValues {
+    /// The values a field of this type may hold.
+    const VALUES: FieldValues;
+}
+
+impl NamedValues for GameMode {
+    const VALUES: FieldValues = FieldValues::OneOf(&["Easy", "Normal", "Hard", "Ultimate"]);
+}
+
+impl NamedValues for PermittedRace {
+    const VALUES: FieldValues = FieldValues::OneOf(&[
+        "Human",
+        "Elf",
+        "AllLight",
+        "DeathEater",
+        "Vail",
+        "AllFury",
+        "AllFactions",
+        "None",
+    ]);
+}
+
+impl NamedValues for ElementType {
+    const VALUES: FieldValues = FieldValues::OneOf(&["None", "Fire", "Water", "Earth", "Wind"]);
+}
+
+/// Defines a set of flags which is stored as a bitmask in memory, but encoded as one boolean byte
+/// per flag, in the order the flags are declared.
+macro_rules! flag_mask {
+    (
+        $(#[$meta:meta])*
+        $ident:ident($bits:ty) {
+            $(
+                $(#[$flag_meta:meta])*
+                $flag:ident = $bit:literal
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Default, Copy, Clone, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+        pub struct $ident($bits);
+
+        impl $ident {
+            $(
+                $(#[$flag_meta])*
+                pub const $flag: Self = Self(1 << $bit);
+            )*
+
+            /// The number of flags in this mask, which is also the number of bytes it's encoded as.
+            pub const LEN: usize = [$($bit),*].len();
+
+            /// Creates an empty mask, with no flags set.
+            pub fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Gets the raw bits of this mask, where bit `n` is the `n`th flag.
+            pub fn bits(&self) -> $bits {
+                self.0
+            }
+
+            /// Checks if every flag in another mask is set in this mask.
+            ///
+            /// # Arguments
+            /// * `other`   - The flags to check.
+            pub fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Sets every flag in another mask.
+            ///
+            /// # Arguments
+            /// * `other`   - The flags to set.
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Clears every flag in another mask.
+            ///
+            /// # Arguments
+            /// * `other`   - The flags to clear.
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            /// Checks if no flags are set.
+            pub fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+        }
+
+        impl NamedValues for $ident {
+            const VALUES: FieldValues = FieldValues::FlagsOf(&[$(stringify!($flag)),*]);
+        }
+
+        impl std::ops::BitOr for $ident {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl From<[bool; $ident::LEN]> for $ident {
+            fn from(flags: [bool; $ident::LEN]) -> Self {
+                let bits = flags
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, set)| **set)
+                    .fold(0, |bits, (bit, _)| bits | (1 << bit));
+                Self(bits)
+            }
+        }
+
+        impl From<$ident> for [bool; $ident::LEN] {
+            fn from(mask: $ident) -> Self {
+                std::array::from_fn(|bit| mask.0 & (1 << bit) != 0)
+            }
+        }
+
+        impl Deserialize for $ident {
+            type Error = std::io::Error;
+
+            fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+            where
+                T: Read + ReadBytesExt,
+                Self: Sized,
+            {
+                let mut flags = [false; $ident::LEN];
+                for flag in flags.iter_mut() {
+                    *flag = src.read_u8()? != 0;
+                }
+                Ok(Self::from(flags))
+            }
+        }
+
+        impl Serialize for $ident {
+            type Error = std::io::Error;
+
+            fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+            where
+                T: Write + WriteBytesExt,
+            {
+                for flag in <[bool; $ident::LEN]>::from(*self) {
+                    dst.write_u8(u8::from(flag))?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+flag_mask!(
+    /// The classes which may use an item or skill.
+    ClassMask(u8) {
+        FIGHTER = 0,
+        DEFENDER = 1,
+        RANGER = 2,
+        ARCHER = 3,
+        MAGE = 4,
+        PRIEST = 5,
+    }
+);
+
+flag_mask!(
+    /// The weapons a skill may be used with.
+    WeaponMask(u16) {
+        ONE_HANDED_SWORD = 0,
+        TWO_HANDED_SWORD = 1,
+        ONE_HANDED_AXE = 2,
+        TWO_HANDED_AXE = 3,
+        DUAL_SWORDS = 4,
+        SPEAR = 5,
+        ONE_HANDED_MACE = 6,
+        TWO_HANDED_MACE = 7,
+        REVERSE_SWORD = 8,
+        DAGGER = 9,
+        JAVELIN = 10,
+        STAFF = 11,
+        BOW = 12,
+        CROSSBOW = 13,
+        FIST_WEAPON = 14,
+        SHIELD = 15,
+    }
+);
+
+impl GameMode {
+    /// Gets the byte representation of this game mode. The ids are the same in every supported
+    /// game version.
+    fn id(&self) -> u8 {
+        match *self {
+            Self::Easy => 0,
+            Self::Normal => 1,
+            Self::Hard => 2,
+            Self::Ultimate => 3,
+        }
+    }
+
+    /// Maps a `u8` to a [GameMode]. If none is found, this returns `None`.
+    ///
+    /// # Arguments
+    /// * `id`  - The game mode id.
+    fn for_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Easy),
+            1 => Some(Self::Normal),
+            2 => Some(Self::Hard),
+            3 => Some(Self::Ultimate),
+            _ => None,
+        }
+    }
+}
+
+impl PermittedRace {
+    /// Gets the byte representation of this permitted race. The ids are the same in every
+    /// supported game version.
+    fn id(&self) -> u8 {
+        match *self {
+            Self::Human => 0,
+            Self::Elf => 1,
+            Self::AllLight => 2,
+            Self::DeathEater => 3,
+            Self::Vail => 4,
+            Self::AllFury => 5,
+            Self::AllFactions => 6,
+            Self::None => 7,
+        }
+    }
+
+    /// Maps a `u8` to a [PermittedRace]. If none is found, this returns `None`.
+    ///
+    /// # Arguments
+    /// * `id`  - The permitted race id.
+    fn for_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Human),
+            1 => Some(Self::Elf),
+            2 => Some(Self::AllLight),
+            3 => Some(Self::DeathEater),
+            4 => Some(Self::Vail),
+            5 => Some(Self::AllFury),
+            6 => Some(Self::AllFactions),
+            7 => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+
 impl Deserialize for GameMode {
     type Error = std::io::Error;
 
-    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
     where
         T: Read + ReadBytesExt,
         Self: Sized,
     {
         let mode = src.read_u8()?;
-        match mode {
-            0 => Ok(GameMode::Easy),
-            1 => Ok(Self::Normal),
-            2 => Ok(Self::Hard),
-            3 => Ok(Self::Ultimate),
-            _ => Err(std::io::Error::new(
+        Self::for_id(mode).ok_or_else(|| {
+            std::io::Error::new(
                 ErrorKind::InvalidInput,
-                format!("invalid game mode {mode}"),
-            )),
-        }
+                format!("invalid game mode {mode} for {version}"),
+            )
+        })
     }
 }
 
@@ -72,39 +347,25 @@ impl Serialize for GameMode {
     where
         T: Write + WriteBytesExt,
     {
-        let id = match *self {
-            Self::Easy => 0,
-            Self::Normal => 1,
-            Self::Hard => 2,
-            Self::Ultimate => 3,
-        };
-        dst.write_u8(id)
+        dst.write_u8(self.id())
     }
 }
 
 impl Deserialize for PermittedRace {
     type Error = std::io::Error;
 
-    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
     where
         T: Read + ReadBytesExt,
         Self: Sized,
     {
         let permitted = src.read_u8()?;
-        match permitted {
-            0 => Ok(Self::Human),
-            1 => Ok(Self::Elf),
-            2 => Ok(Self::AllLight),
-            3 => Ok(Self::DeathEater),
-            4 => Ok(Self::Vail),
-            5 => Ok(Self::AllFury),
-            6 => Ok(Self::AllFactions),
-            7 => Ok(Self::None),
-            _ => Err(std::io::Error::new(
+        Self::for_id(permitted).ok_or_else(|| {
+            std::io::Error::new(
                 ErrorKind::InvalidInput,
-                format!("invalid permitted race {permitted}"),
-            )),
-        }
+                format!("invalid permitted race {permitted} for {version}"),
+            )
+        })
     }
 }
 
@@ -115,17 +376,7 @@ impl Serialize for PermittedRace {
     where
         T: Write + WriteBytesExt,
     {
-        let id = match *self {
-            Self::Human => 0,
-            Self::Elf => 1,
-            Self::AllLight => 2,
-            Self::DeathEater => 3,
-            Self::Vail => 4,
-            Self::AllFury => 5,
-            Self::AllFactions => 6,
-            Self::None => 7,
-        };
-        dst.write_u8(id)
+        dst.write_u8(self.id())
     }
 }
 
@@ -172,3 +423,82 @@ impl Serialize for ElementType {
         dst.write_u8(id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::types::{ClassMask, GameMode, PermittedRace, WeaponMask};
+    use crate::io::{Deserialize, GameVersion, Serialize};
+    use std::io::Cursor;
+
+    #[test]
+    fn permitted_race_tables() {
+        for version in GameVersion::all() {
+            for id in 0..=7 {
+                let race =
+                    PermittedRace::versioned_deserialize(&mut Cursor::new([id]), *version).unwrap();
+                let mut dst = Vec::new();
+                race.versioned_serialize(&mut dst, *version).unwrap();
+                assert_eq!(dst, [id]);
+            }
+
+            assert!(PermittedRace::versioned_deserialize(&mut Cursor::new([8]), *version).is_err());
+        }
+
+        // The table is documented as identical in every supported version, so an id must never
+        // decode differently between Ep4 and Ep6.
+        for id in 0..=7 {
+            let decoded = GameVersion::all()
+                .iter()
+                .map(|version| {
+                    PermittedRace::versioned_deserialize(&mut Cursor::new([id]), *version).unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert!(decoded.iter().all(|race| *race == decoded[0]));
+        }
+    }
+
+    #[test]
+    fn game_mode_tables() {
+        for version in GameVersion::all() {
+            for id in 0..=3 {
+                let mode =
+                    GameMode::versioned_deserialize(&mut Cursor::new([id]), *version).unwrap();
+                let mut dst = Vec::new();
+                mode.versioned_serialize(&mut dst, *version).unwrap();
+                assert_eq!(dst, [id]);
+            }
+
+            assert!(GameMode::versioned_deserialize(&mut Cursor::new([4]), *version).is_err());
+        }
+    }
+
+    #[test]
+    fn flag_masks_keep_wire_order() {
+        let flags = [true, false, false, true, false, true];
+        let mask = ClassMask::from(flags);
+        assert_eq!(
+            mask,
+            ClassMask::FIGHTER | ClassMask::ARCHER | ClassMask::PRIEST
+        );
+        assert_eq!(<[bool; ClassMask::LEN]>::from(mask), flags);
+
+        let mut dst = Vec::new();
+        mask.versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        assert_eq!(dst, [1, 0, 0, 1, 0, 1]);
+
+        let bytes = [0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1];
+        let mut src = Cursor::new(bytes);
+        let mut weapons = WeaponMask::versioned_deserialize(&mut src, GameVersion::Ep6).unwrap();
+        assert!(weapons.contains(WeaponMask::BOW | WeaponMask::CROSSBOW));
+        assert!(!weapons.contains(WeaponMask::STAFF));
+
+        weapons.insert(WeaponMask::STAFF);
+        weapons.remove(WeaponMask::ONE_HANDED_AXE);
+        let mut dst = Vec::new();
+        weapons
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        assert_eq!(dst, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 1]);
+    }
+}
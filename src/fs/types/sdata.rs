@@ -1,74 +1,366 @@
-use crate::io::{Deserialize, GameVersion, ShaiyaReadExt};
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, TrailingBytes};
 
-use byteorder::ReadBytesExt;
-use cipher::BlockDecrypt;
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use cipher::{BlockDecrypt, BlockEncrypt};
 use kisaseed::{Block, Key, SEED};
-use std::io::Read;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Write};
 
-const SEED_SIGNATURE: &str = "0001CBCEBC5B2784D3FC9A2A9DB84D1C3FEB6E99";
+/// The signature at the start of every SEED-encrypted `.SData` file.
+pub const SEED_SIGNATURE: &str = "0001CBCEBC5B2784D3FC9A2A9DB84D1C3FEB6E99";
 
-const SHAIYA_SEED_KEY: &[u32] = &[
+/// The length of the signature at the start of an encrypted file.
+pub(crate) const SIGNATURE_LENGTH: usize = SEED_SIGNATURE.len();
+
+/// The size of the header which precedes the encrypted payload. This contains the signature, the
+/// checksum and size of the decrypted payload, and some padding.
+const SEED_HEADER_SIZE: usize = 64;
+
+/// The size of a single SEED block.
+const SEED_BLOCK_SIZE: usize = 16;
+
+/// The SEED key used by the retail client to encrypt `.SData` files. Some regional and private
+/// server clients use a different key, which can be supplied with [SData::deserialize_with_key].
+pub const SHAIYA_SEED_KEY: &[u32; 32] = &[
     0x79F5DBDE, 0x345AC74A, 0x0F482438, 0x0131F493, 0x81A8500C, 0x0659BDCF, 0x26FF71C1, 0x86E9A5CB,
     0xCA6FB745, 0x50E2C1AE, 0x381DDAE1, 0xC3402821, 0x3FECCB4A, 0x3E0BE066, 0x372582FF, 0x826317E3,
     0xA47B5369, 0xC9093C0E, 0xE16C9CB1, 0x2E27228E, 0x84E2D1CD, 0xC840F818, 0x44AEA6F8, 0xD298548D,
     0x2040CA27, 0x4B4E2B78, 0x64F0A045, 0xC171A8DA, 0x384855ED, 0xC033578B, 0x2A8703C7, 0xF15DA3A7,
 ];
 
+/// The payload of an `.SData` file, along with the metadata from its SEED container. Files which
+/// weren't encrypted have no container, so their metadata describes the payload as it would be
+/// written by [SData::serialize_encrypted].
 #[derive(Debug)]
 pub struct SData {
+    pub signature: [u8; SIGNATURE_LENGTH],
+    pub stored_checksum: u32,
+    pub decrypted_size: usize,
     pub data: Vec<u8>,
+    pub was_encrypted: bool,
 }
 
-impl Deserialize for SData {
-    type Error = std::io::Error;
+impl SData {
+    /// Creates an unencrypted `.SData` payload, with the metadata the retail client expects when
+    /// it is encrypted.
+    ///
+    /// # Arguments
+    /// * `data`    - The payload.
+    pub fn new(data: Vec<u8>) -> Self {
+        let mut signature = [0; SIGNATURE_LENGTH];
+        signature.copy_from_slice(SEED_SIGNATURE.as_bytes());
 
-    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+        Self {
+            signature,
+            stored_checksum: crc32fast::hash(&data),
+            decrypted_size: data.len(),
+            data,
+            was_encrypted: false,
+        }
+    }
+
+    /// Reads an `.SData` file, decrypting it with a given SEED key if it is encrypted. The
+    /// checksum of the decrypted payload is verified, so decrypting with the wrong key results in
+    /// an error rather than garbage data.
+    ///
+    /// # Arguments
+    /// * `src` - The source buffer.
+    /// * `key` - The SEED key.
+    pub fn deserialize_with_key<T>(src: &mut T, key: &[u32; 32]) -> Result<Self, std::io::Error>
     where
         T: Read + ReadBytesExt,
-        Self: Sized,
     {
-        let mut data = src.consume_all();
-        if !is_encrypted(&data) {
-            return Ok(Self { data });
+        let data = src.consume_all();
+        if !is_shaiya_encrypted(&data) {
+            return Ok(Self::new(data));
         }
 
-        let _checksum = u32::from_le_bytes(data[40..44].try_into().unwrap());
+        if data.len() < SEED_HEADER_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "encrypted file is too short to contain a header ({} bytes)",
+                    data.len()
+                ),
+            ));
+        }
+
+        let payload_size = data.len() - SEED_HEADER_SIZE;
+        if !payload_size.is_multiple_of(SEED_BLOCK_SIZE) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "encrypted payload size {payload_size} is not a multiple of the block size"
+                ),
+            ));
+        }
+
+        let signature = data[..SIGNATURE_LENGTH].try_into().unwrap();
+        let checksum = u32::from_le_bytes(data[40..44].try_into().unwrap());
         let decrypted_size = u32::from_le_bytes(data[44..48].try_into().unwrap()) as usize;
 
-        let encrypted = &mut data[64..];
-        let seed = SEED::with_key(*Key::from_slice(SHAIYA_SEED_KEY));
+        let encrypted = &data[SEED_HEADER_SIZE..];
+        let seed = SEED::with_key(*Key::from_slice(key));
 
         let mut blocks = encrypted
-            .chunks_mut(16)
-            .map(|chunk| Block::from(unsafe { *(chunk.as_mut_ptr() as *const [u8; 16]) }))
-            .collect::<Vec<_>>();
+            .chunks(SEED_BLOCK_SIZE)
+            .map(|chunk| {
+                <[u8; SEED_BLOCK_SIZE]>::try_from(chunk)
+                    .map(Block::from)
+                    .map_err(|_| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("encrypted block is {} bytes", chunk.len()),
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
         seed.decrypt_blocks(&mut blocks);
 
+        let mut data = blocks
+            .iter()
+            .fold(Vec::with_capacity(decrypted_size), |mut acc, e| {
+                acc.extend_from_slice(e.as_slice());
+                acc
+            });
+
+        let actual = data.get(..decrypted_size).map(crc32fast::hash);
+        if actual != Some(checksum) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "decrypted checksum mismatch (is the SEED key correct?)",
+            ));
+        }
+
+        // The final block is padded to the block size, and the padding isn't part of the payload.
+        data.truncate(decrypted_size);
         Ok(Self {
-            data: blocks
-                .iter()
-                .fold(Vec::with_capacity(decrypted_size), |mut acc, e| {
-                    acc.extend_from_slice(e.as_slice());
-                    acc
-                }),
+            signature,
+            stored_checksum: checksum,
+            decrypted_size,
+            data,
+            was_encrypted: true,
         })
     }
+
+    /// Writes the payload wrapped in the SEED-encrypted container that the retail client expects.
+    /// The payload is padded with zeroes to the cipher's block size. The signature is preserved,
+    /// but the checksum and size are always computed from the current payload.
+    ///
+    /// # Arguments
+    /// * `dst` - The destination buffer.
+    pub fn serialize_encrypted<T>(&self, dst: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        dst.write_all(&self.signature)?;
+        dst.write_u32::<LE>(crc32fast::hash(&self.data))?;
+        dst.write_u32::<LE>(self.data.len() as u32)?;
+        dst.write_all(&[0; SEED_HEADER_SIZE - 48])?;
+
+        let seed = SEED::with_key(*Key::from_slice(SHAIYA_SEED_KEY));
+        let mut blocks = self
+            .data
+            .chunks(16)
+            .map(|chunk| {
+                let mut block = Block::default();
+                block[..chunk.len()].copy_from_slice(chunk);
+                block
+            })
+            .collect::<Vec<_>>();
+        seed.encrypt_blocks(&mut blocks);
+
+        for block in &blocks {
+            dst.write_all(block.as_slice())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the payload as plaintext, without the SEED container. This is only understood by
+    /// clients which have had the encryption removed.
+    ///
+    /// # Arguments
+    /// * `dst` - The destination buffer.
+    pub fn serialize_plain<T>(&self, dst: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        dst.write_all(&self.data)
+    }
+}
+
+impl Serialize for SData {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        self.serialize_encrypted(dst)
+    }
 }
 
-fn is_encrypted(buf: &[u8]) -> bool {
-    if buf.len() < SEED_SIGNATURE.len() {
-        return false;
+impl Deserialize for SData {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        Self::deserialize_with_key(src, SHAIYA_SEED_KEY)
+    }
+}
+
+/// A type which is stored as the payload of an `.SData` file. Implementors only parse the decrypted
+/// payload with [SdataBacked::from_decrypted], and the provided methods take care of reading and
+/// decrypting the file around it.
+pub trait SdataBacked: Sized {
+    /// Parses the value from the decrypted payload of an `.SData` file.
+    ///
+    /// # Arguments
+    /// * `src`     - The cursor over the decrypted payload.
+    /// * `version` - The game version.
+    fn from_decrypted(
+        src: &mut Cursor<&[u8]>,
+        version: GameVersion,
+    ) -> Result<Self, std::io::Error>;
+
+    /// Keeps the bytes the value was read from, before they were decrypted. This is called after
+    /// every successful read, and does nothing by default.
+    ///
+    /// # Arguments
+    /// * `original`    - The bytes the value was read from.
+    fn retain_original(&mut self, _original: Vec<u8>) {}
+
+    /// Reads the value from an `.SData` file, decrypting it with a given SEED key if it is
+    /// encrypted.
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `version` - The game version.
+    /// * `key`     - The SEED key.
+    /// * `strict`  - If the value must take up the whole decrypted payload.
+    fn deserialize_sdata<T>(
+        src: &mut T,
+        version: GameVersion,
+        key: &[u32; 32],
+        strict: bool,
+    ) -> Result<Self, std::io::Error>
+    where
+        T: Read + ReadBytesExt,
+    {
+        Self::deserialize_sdata_with(src, key, strict, |src| Self::from_decrypted(src, version))
     }
 
-    String::from_utf8_lossy(&buf[..SEED_SIGNATURE.len()]).eq(SEED_SIGNATURE)
+    /// Reads the value from an `.SData` file like [SdataBacked::deserialize_sdata], but parses the
+    /// decrypted payload with a given function rather than [SdataBacked::from_decrypted].
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `key`     - The SEED key.
+    /// * `strict`  - If the value must take up the whole decrypted payload.
+    /// * `parse`   - Parses the value from the decrypted payload.
+    fn deserialize_sdata_with<T, F>(
+        src: &mut T,
+        key: &[u32; 32],
+        strict: bool,
+        parse: F,
+    ) -> Result<Self, std::io::Error>
+    where
+        T: Read + ReadBytesExt,
+        F: FnOnce(&mut Cursor<&[u8]>) -> Result<Self, std::io::Error>,
+    {
+        let original = src.consume_all();
+        let decrypted = SData::deserialize_with_key(&mut original.as_slice(), key)?;
+        let mut payload = Cursor::new(decrypted.data.as_slice());
+
+        let mut value = parse(&mut payload)?;
+        if strict {
+            ensure_consumed(&payload)?;
+        }
+        value.retain_original(original);
+        Ok(value)
+    }
+}
+
+/// Checks if a buffer is SEED-encrypted, by checking if it starts with [SEED_SIGNATURE]. This only
+/// looks at the signature, so the rest of the buffer may still fail to decrypt.
+///
+/// # Arguments
+/// * `buf` - The buffer.
+pub fn is_shaiya_encrypted(buf: &[u8]) -> bool {
+    buf.starts_with(SEED_SIGNATURE.as_bytes())
+}
+
+/// Converts a count to the `u32` it's encoded as, failing rather than truncating if it's too large.
+///
+/// # Arguments
+/// * `count`   - The count.
+/// * `what`    - A description of what is being counted, for the error message.
+pub(crate) fn checked_count(count: usize, what: &str) -> Result<u32, std::io::Error> {
+    u32::try_from(count).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{what} {count} does not fit in a u32"),
+        )
+    })
+}
+
+/// Merges one set of grouped records over another. Each record in `overlay` replaces the record at
+/// the same position in `base`, with the group being extended with default records if the
+/// position is past its end. Records which are placeholders in `overlay` are skipped, so an overlay
+/// only needs to contain the records it changes.
+///
+/// # Arguments
+/// * `base`            - The records to merge into.
+/// * `overlay`         - The records to merge.
+/// * `is_placeholder`  - Checks if a record is an unused placeholder.
+pub(crate) fn merge_records<R, F>(
+    base: &mut BTreeMap<usize, Vec<R>>,
+    overlay: BTreeMap<usize, Vec<R>>,
+    is_placeholder: F,
+) where
+    R: Default,
+    F: Fn(&R) -> bool,
+{
+    for (group, records) in overlay {
+        let existing = base.entry(group).or_default();
+        for (index, record) in records.into_iter().enumerate() {
+            if is_placeholder(&record) {
+                continue;
+            }
+
+            if index >= existing.len() {
+                existing.resize_with(index + 1, R::default);
+            }
+            existing[index] = record;
+        }
+    }
+}
+
+/// Checks that every byte of a decrypted payload was consumed while parsing its records. Bytes
+/// left over after the declared records usually mean that a field layout doesn't match the file.
+///
+/// # Arguments
+/// * `src` - The cursor over the decrypted payload.
+pub(crate) fn ensure_consumed<B>(src: &Cursor<B>) -> Result<(), TrailingBytes>
+where
+    B: AsRef<[u8]>,
+{
+    let count = src.get_ref().as_ref().len() - src.position() as usize;
+    match count {
+        0 => Ok(()),
+        count => Err(TrailingBytes { count }),
+    }
 }
 
 pub(crate) fn ep6_or_above(version: GameVersion) -> bool {
-    version >= GameVersion::Ep6
+    version.at_least(GameVersion::Ep6)
 }
 
 pub(crate) fn ep6v2_or_above(version: GameVersion) -> bool {
-    version >= GameVersion::Ep6v2
+    version.at_least(GameVersion::Ep6v2)
 }
 
 macro_rules! user_type {
@@ -78,57 +370,67 @@ macro_rules! user_type {
 }
 
 macro_rules! user_type_readable {
-    ($src:ident, $version:ident, u8, $if:expr) => {
+    ($src:ident, $version:ident, $endian:ident, u8, $if:expr) => {
         if $if($version) {
             $src.read_u8()?
         } else {
             0
         }
     };
-    ($src:ident, $version:ident, u8) => {
+    ($src:ident, $version:ident, $endian:ident, u8) => {
         $src.read_u8()?
     };
-    ($src:ident, $version:ident, bool) => {
+    ($src:ident, $version:ident, $endian:ident, bool) => {
         1 <= $src.read_u8()?
     };
-    ($src:ident, $version:ident, bool, $if:expr) => {
+    ($src:ident, $version:ident, $endian:ident, bool, $if:expr) => {
         if $if($version) {
             1 <= $src.read_u8()?
         } else {
             false
         }
     };
-    ($src:ident, $version:ident, u16) => {
-        $src.read_u16::<byteorder::LittleEndian>()?
+    ($src:ident, $version:ident, $endian:ident, u16) => {
+        $src.read_u16::<byteorder::$endian>()?
     };
-    ($src:ident, $version:ident, u16, $if:expr) => {
+    ($src:ident, $version:ident, $endian:ident, u16, $if:expr) => {
         if $if($version) {
-            $src.read_u16::<byteorder::LittleEndian>()?
+            $src.read_u16::<byteorder::$endian>()?
         } else {
             0
         }
     };
-    ($src:ident, $version:ident, u32) => {
-        $src.read_u32::<byteorder::LittleEndian>()?
+    ($src:ident, $version:ident, $endian:ident, u32) => {
+        $src.read_u32::<byteorder::$endian>()?
     };
-    ($src:ident, $version:ident, u32, $if:expr) => {
+    ($src:ident, $version:ident, $endian:ident, u32, $if:expr) => {
         if $if($version) {
-            $src.read_u32::<byteorder::LittleEndian>()?
+            $src.read_u32::<byteorder::$endian>()?
         } else {
             0
         }
     };
-    ($src:ident, $version:ident, String) => {
+    ($src:ident, $version:ident, BigEndian, String) => {
+        $src.read_length_prefixed_string_be()?
+    };
+    ($src:ident, $version:ident, BigEndian, String, $if:expr) => {
+        if $if($version) {
+            $src.read_length_prefixed_string_be()?
+        } else {
+            String::new()
+        }
+    };
+    ($src:ident, $version:ident, $endian:ident, String) => {
         $src.read_length_prefixed_string()?
     };
-    ($src:ident, $version:ident, String, $if:expr) => {
+    ($src:ident, $version:ident, $endian:ident, String, $if:expr) => {
         if $if($version) {
             $src.read_length_prefixed_string()?
         } else {
             String::new()
         }
     };
-    ($src:ident, $version:ident, Vec <$inner:ident>, $len:expr) => {{
+    ($src:ident, $version:ident, $endian:ident, Vec <$inner:ident>, $len:expr) => {{
         let length = $len($version);
         let mut vec = Vec::with_capacity(length);
         for _ in 0..length {
@@ -137,12 +439,12 @@ macro_rules! user_type_readable {
         }
         vec
     }};
-    ($src:ident, $version:ident, $typ:ty) => {
-        <$typ>::versioned_deserialize($src, $version).unwrap()
+    ($src:ident, $version:ident, $endian:ident, $typ:ty) => {
+        <$typ>::versioned_deserialize($src, $version)?
     };
-    ($src:ident, $version:ident, $typ:ty, $if:expr) => {
+    ($src:ident, $version:ident, $endian:ident, $typ:ty, $if:expr) => {
         if $if($version) {
-            <$typ>::versioned_deserialize($src, $version).unwrap()
+            <$typ>::versioned_deserialize($src, $version)?
         } else {
             <$typ>::default()
         }
@@ -150,47 +452,55 @@ macro_rules! user_type_readable {
 }
 
 macro_rules! user_type_writeable {
-    ($dst:ident, $version:ident, u8, $value:expr, $if:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, u8, $value:expr, $if:expr) => {
         if $if($version) {
             $dst.write_u8(*$value)?
         }
     };
-    ($dst:ident, $version:ident, u8, $value:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, u8, $value:expr) => {
         $dst.write_u8(*$value)?
     };
-    ($dst:ident, $version:ident, bool, $value:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, bool, $value:expr) => {
         $dst.write_u8(if *$value { 1 } else { 0 })?
     };
-    ($dst:ident, $version:ident, bool, $value:expr, $if:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, bool, $value:expr, $if:expr) => {
         if $if($version) {
             $dst.write_u8(if *$value { 1 } else { 0 })?
         }
     };
-    ($dst:ident, $version:ident, u16, $value:expr) => {
-        $dst.write_u16::<byteorder::LittleEndian>(*$value)?
+    ($dst:ident, $version:ident, $endian:ident, u16, $value:expr) => {
+        $dst.write_u16::<byteorder::$endian>(*$value)?
+    };
+    ($dst:ident, $version:ident, $endian:ident, u16, $value:expr, $if:expr) => {
+        if $if($version) {
+            $dst.write_u16::<byteorder::$endian>(*$value)?
+        }
+    };
+    ($dst:ident, $version:ident, $endian:ident, u32, $value:expr) => {
+        $dst.write_u32::<byteorder::$endian>(*$value)?
     };
-    ($dst:ident, $version:ident, u16, $value:expr, $if:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, u32, $value:expr, $if:expr) => {
         if $if($version) {
-            $dst.write_u16::<byteorder::LittleEndian>(*$value)?
+            $dst.write_u32::<byteorder::$endian>(*$value)?
         }
     };
-    ($dst:ident, $version:ident, u32, $value:expr) => {
-        $dst.write_u32::<byteorder::LittleEndian>(*$value)?
+    ($dst:ident, $version:ident, BigEndian, String, $value:expr) => {
+        $dst.write_length_prefixed_string_be($value)?
     };
-    ($dst:ident, $version:ident, u32, $value:expr, $if:expr) => {
+    ($dst:ident, $version:ident, BigEndian, String, $value:expr, $if:expr) => {
         if $if($version) {
-            $dst.write_u32::<byteorder::LittleEndian>(*$value)?
+            $dst.write_length_prefixed_string_be($value)?
         }
     };
-    ($dst:ident, $version:ident, String, $value:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, String, $value:expr) => {
         $dst.write_length_prefixed_string($value)?
     };
-    ($dst:ident, $version:ident, String, $value:expr, $if:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, String, $value:expr, $if:expr) => {
         if $if($version) {
             $dst.write_length_prefixed_string($value)?
         }
     };
-    ($dst:ident, $version:ident, Vec <$inner:ident>, $value:expr, $len:expr) => {{
+    ($dst:ident, $version:ident, $endian:ident, Vec <$inner:ident>, $value:expr, $len:expr) => {{
         let length = $len($version);
         for idx in 0..length {
             if idx >= $value.len() {
@@ -201,19 +511,57 @@ macro_rules! user_type_writeable {
             }
         }
     }};
-    ($dst:ident, $version:ident, $typ:ty, $value:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, $typ:ty, $value:expr) => {
         $value.versioned_serialize($dst, $version)?
     };
-    ($dst:ident, $version:ident, $typ:ty, $value:expr, $if:expr) => {
+    ($dst:ident, $version:ident, $endian:ident, $typ:ty, $value:expr, $if:expr) => {
         if $if($version) {
             $value.versioned_serialize($dst, $version)?
         }
     };
 }
 
+/// Gets the [FieldValues](crate::fs::types::FieldValues) of a record field. Primitive fields may
+/// hold any value, and every other field type must implement
+/// [NamedValues](crate::fs::types::NamedValues).
+macro_rules! field_values {
+    (u8) => {
+        $crate::fs::types::FieldValues::Any
+    };
+    (u16) => {
+        $crate::fs::types::FieldValues::Any
+    };
+    (u32) => {
+        $crate::fs::types::FieldValues::Any
+    };
+    (bool) => {
+        $crate::fs::types::FieldValues::Any
+    };
+    (String) => {
+        $crate::fs::types::FieldValues::Any
+    };
+    (Vec <$inner:ident>) => {
+        $crate::fs::types::FieldValues::Any
+    };
+    ($typ:ident) => {
+        <$typ as $crate::fs::types::NamedValues>::VALUES
+    };
+}
+
+/// Defines a record of an `.SData` file, which is encoded as each of its fields in order. Numbers
+/// and string lengths are little-endian unless the record is declared as `Name: BigEndian`, which
+/// is used by some regional builds. Fields of other types are encoded with their own
+/// implementations, and are unaffected by the record's byte order.
 macro_rules! sdata_record {
     (
         $ident:ident {
+            $($body:tt)*
+        }
+    ) => {
+        sdata_record!($ident: LittleEndian { $($body)* });
+    };
+    (
+        $ident:ident: $endian:ident {
             $(
                 $field:ident $typ:ident $(<$generics:ident>)?
                 $(if($if:expr))?
@@ -240,7 +588,7 @@ macro_rules! sdata_record {
                 Self: Sized
             {
                 $(
-                    let $field = user_type_readable!(src, version, $typ $(<$generics>)? $(,$if)? $(,$len)?);
+                    let $field = user_type_readable!(src, version, $endian, $typ $(<$generics>)? $(,$if)? $(,$len)?);
                 )*
 
                 Ok(Self {
@@ -260,11 +608,29 @@ macro_rules! sdata_record {
                 T: Write + WriteBytesExt
             {
                 $(
-                    user_type_writeable!(dst, version, $typ $(<$generics>)?, &self.$field $(,$if)? $(,$len)?);
+                    user_type_writeable!(dst, version, $endian, $typ $(<$generics>)?, &self.$field $(,$if)? $(,$len)?);
                 )*
                 Ok(())
             }
         }
+
+        impl $crate::io::SerializedLen for $ident {}
+
+        impl $ident {
+            /// Describes every field of this record, in the order the fields are encoded.
+            pub fn field_hints() -> &'static [$crate::fs::types::FieldHint] {
+                const HINTS: &[$crate::fs::types::FieldHint] = &[
+                    $(
+                        $crate::fs::types::FieldHint {
+                            name: stringify!($field),
+                            type_name: concat!(stringify!($typ) $(, "<", stringify!($generics), ">")?),
+                            values: field_values!($typ $(<$generics>)?),
+                        },
+                    )*
+                ];
+                HINTS
+            }
+        }
     };
 }
 
@@ -272,4 +638,219 @@ pub fn is_default<T: Default + PartialEq>(t: &T) -> bool {
     t == &T::default()
 }
 
-pub(crate) use {sdata_record, user_type, user_type_readable, user_type_writeable};
+pub(crate) use {field_values, sdata_record, user_type, user_type_readable, user_type_writeable};
+
+/// Asserts that a type decodes from some bytes and re-encodes to exactly the same bytes, and that
+/// decoding the re-encoded bytes produces an equal value. This catches fields which are read and
+/// written in a different order.
+#[cfg(test)]
+macro_rules! assert_sdata_roundtrip {
+    ($typ:ty, $version:expr, $bytes:expr) => {{
+        use $crate::io::{Deserialize, Serialize};
+
+        let bytes: &[u8] = &$bytes;
+        let version = $version;
+
+        let decoded = <$typ>::versioned_deserialize(&mut std::io::Cursor::new(bytes), version)
+            .expect("failed to decode bytes");
+        let mut encoded = Vec::with_capacity(bytes.len());
+        decoded
+            .versioned_serialize(&mut encoded, version)
+            .expect("failed to encode decoded value");
+        assert_eq!(encoded.as_slice(), bytes, "re-encoded bytes differ");
+
+        let redecoded =
+            <$typ>::versioned_deserialize(&mut std::io::Cursor::new(encoded.as_slice()), version)
+                .expect("failed to decode re-encoded bytes");
+        assert_eq!(redecoded, decoded, "re-decoded value differs");
+    }};
+}
+
+#[cfg(test)]
+pub(crate) use assert_sdata_roundtrip;
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::types::sdata::{is_shaiya_encrypted, SData, SEED_SIGNATURE, SHAIYA_SEED_KEY};
+    use crate::io::{Deserialize, GameVersion, Serialize};
+    use std::io::Cursor;
+
+    mod regional {
+        use crate::fs::types::sdata::ep6_or_above;
+        use crate::io::{GameVersion, ShaiyaReadExt, ShaiyaWriteExt};
+        use byteorder::{ReadBytesExt, WriteBytesExt};
+        use std::io::{Read, Write};
+
+        sdata_record!(RegionalRecord: BigEndian {
+            name        String;
+            level       u16;
+            exp         u32;
+            tradable    bool;
+            grade       u16 if(ep6_or_above);
+        });
+    }
+
+    #[test]
+    fn plain_round_trip() {
+        let sdata = SData::new(vec![1, 0, 0, 0, 5, 6, 7]);
+
+        let mut dst = Vec::new();
+        sdata.serialize_plain(&mut dst).unwrap();
+        assert!(!is_shaiya_encrypted(&dst));
+
+        let decoded = SData::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(decoded.data, sdata.data);
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let sdata = SData::new((0..40).collect());
+
+        let mut dst = Vec::new();
+        sdata.serialize(&mut dst).unwrap();
+        assert!(is_shaiya_encrypted(&dst));
+        assert_eq!(dst.len(), 64 + 48);
+
+        let decoded = SData::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(decoded.data, sdata.data);
+    }
+
+    #[test]
+    fn wrong_key_fails_checksum() {
+        let sdata = SData::new((0..40).collect());
+
+        let mut dst = Vec::new();
+        sdata.serialize(&mut dst).unwrap();
+
+        let mut key = *SHAIYA_SEED_KEY;
+        key[0] ^= 0xFFFF;
+        let err = SData::deserialize_with_key(&mut Cursor::new(dst.as_slice()), &key).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let decoded =
+            SData::deserialize_with_key(&mut Cursor::new(dst.as_slice()), SHAIYA_SEED_KEY).unwrap();
+        assert!(decoded.data.starts_with(&sdata.data));
+    }
+
+    #[test]
+    fn signature_without_header() {
+        let mut data = SEED_SIGNATURE.as_bytes().to_vec();
+        data.extend_from_slice(&[0; 10]);
+        assert!(is_shaiya_encrypted(&data));
+
+        let err = SData::deserialize(&mut Cursor::new(data.as_slice())).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn misaligned_payload() {
+        let sdata = SData::new((0..40).collect());
+
+        let mut dst = Vec::new();
+        sdata.serialize(&mut dst).unwrap();
+        dst.truncate(dst.len() - 5);
+
+        let err = SData::deserialize(&mut Cursor::new(dst.as_slice())).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decrypted_output_is_trimmed() {
+        let sdata = SData::new((0..21).collect());
+
+        let mut dst = Vec::new();
+        sdata.serialize(&mut dst).unwrap();
+        assert_eq!(dst.len(), 64 + 32);
+
+        let decrypted_size = u32::from_le_bytes(dst[44..48].try_into().unwrap()) as usize;
+        let decoded = SData::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(decoded.data.len(), decrypted_size);
+        assert_eq!(decoded.data, sdata.data);
+    }
+
+    #[test]
+    fn container_metadata_is_populated() {
+        let sdata = SData::new((0..21).collect());
+        assert!(!sdata.was_encrypted);
+        assert_eq!(sdata.signature, SEED_SIGNATURE.as_bytes());
+
+        let mut dst = Vec::new();
+        sdata.serialize(&mut dst).unwrap();
+
+        let decoded = SData::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert!(decoded.was_encrypted);
+        assert_eq!(decoded.signature, dst[..40]);
+        assert_eq!(decoded.stored_checksum, crc32fast::hash(&sdata.data));
+        assert_eq!(decoded.decrypted_size, 21);
+
+        let mut reencrypted = Vec::new();
+        decoded.serialize(&mut reencrypted).unwrap();
+        assert_eq!(reencrypted, dst);
+    }
+
+    #[test]
+    fn decrypts_known_ciphertext() {
+        let mut src = SEED_SIGNATURE.as_bytes().to_vec();
+        src.extend_from_slice(&[0x15, 0xC0, 0xF6, 0xCB, 0x27, 0x00, 0x00, 0x00]);
+        src.resize(64, 0);
+        src.extend_from_slice(&[
+            0xDA, 0xA1, 0xD5, 0xAE, 0x4B, 0x7A, 0x09, 0x68, 0x00, 0xF8, 0x5D, 0x00, 0xB5, 0x0D,
+            0x83, 0x17, 0xBC, 0x8F, 0xCC, 0x57, 0x2F, 0xAE, 0x47, 0x6F, 0x24, 0x81, 0xA7, 0x23,
+            0x2D, 0xB8, 0xFC, 0x6A, 0xF8, 0x68, 0x83, 0x9C, 0xA8, 0x16, 0x65, 0x66, 0x6D, 0xA6,
+            0x88, 0x4D, 0x6A, 0xC3, 0x32, 0x99,
+        ]);
+
+        let decoded = SData::deserialize(&mut Cursor::new(src.as_slice())).unwrap();
+        assert_eq!(decoded.data, b"Epeiros SData fixture spanning 3 blocks");
+    }
+
+    #[test]
+    fn big_endian_record_round_trip() {
+        let record = regional::RegionalRecord {
+            name: "Sword".to_owned(),
+            level: 0x0102,
+            exp: 0x03040506,
+            tradable: true,
+            grade: 0x0708,
+        };
+
+        let mut dst = Vec::new();
+        record
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        let mut expected = vec![0, 0, 0, 6];
+        expected.extend_from_slice(b"Sword\0");
+        expected.extend_from_slice(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 1, 0x07, 0x08]);
+        assert_eq!(dst, expected);
+
+        let decoded = regional::RegionalRecord::versioned_deserialize(
+            &mut Cursor::new(dst.as_slice()),
+            GameVersion::Ep6,
+        )
+        .unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(regional::RegionalRecord::field_hints().len(), 5);
+
+        let mut dst = Vec::new();
+        record
+            .versioned_serialize(&mut dst, GameVersion::Ep5)
+            .unwrap();
+        assert_eq!(
+            &dst[dst.len() - 7..],
+            [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 1]
+        );
+    }
+
+    #[test]
+    fn classifies_buffers_by_signature() {
+        let mut encrypted = Vec::new();
+        SData::new(b"payload".to_vec())
+            .serialize_encrypted(&mut encrypted)
+            .unwrap();
+        assert!(is_shaiya_encrypted(&encrypted));
+
+        assert!(!is_shaiya_encrypted(b"payload"));
+        assert!(!is_shaiya_encrypted(&SEED_SIGNATURE.as_bytes()[..20]));
+        assert!(!is_shaiya_encrypted(&[]));
+    }
+}
@@ -1,11 +1,39 @@
 use crate::fs::types::*;
-use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt};
+use crate::io::{
+    Deserialize, GameVersion, Serialize, SerializedLen, ShaiyaReadExt, ShaiyaWriteExt,
+};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::{Cursor, Read, Write};
+use thiserror::Error;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-pub struct SkillData(BTreeMap<usize, Vec<SkillRecord>>);
+/// Every skill record, grouped by skill id. An empty set of skills is written as a zero skill id
+/// count, with no records following it. The [GameVersion] the skills were decoded with is
+/// retained, but is not included when serializing with serde.
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SkillData {
+    records: BTreeMap<usize, Vec<SkillRecord>>,
+    #[serde(skip)]
+    decoded_version: Option<GameVersion>,
+    #[serde(skip)]
+    original: Option<Vec<u8>>,
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(skip)]
+    config: SkillDataConfig,
+}
+
+/// Overrides the layout of a skill table, for servers which don't use the retail limits. The
+/// default configuration matches the retail client for every [GameVersion].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SkillDataConfig {
+    /// The number of ranks every skill has. If this is `None`, the retail rank count for the
+    /// [GameVersion] is used.
+    pub ranks_per_skill: Option<usize>,
+    /// The highest skill id to accept when reading a file. See [DEFAULT_MAX_SKILL_ID].
+    pub max_skill_id: usize,
+}
 
 sdata_record!(SkillRecord {
     name                            String;
@@ -18,40 +46,20 @@ sdata_record!(SkillRecord {
     sound                           u16;
     min_level                       u16;
     permitted_races                 PermittedRace;
-    usable_by_fighter               bool;
-    usable_by_defender              bool;
-    usable_by_ranger                bool;
-    usable_by_archer                bool;
-    usable_by_mage                  bool;
-    usable_by_priest                bool;
+    usable_by                       ClassMask;
     min_game_mode                   GameMode;
     skill_point_cost                u8;
     category                        SkillCategory;
     attack_type                     AttackType;
     type_effect                     u8;
     type_detail                     u16;
-    usable_with_one_handed_sword    bool;
-    usable_with_two_handed_sword    bool;
-    usable_with_one_handed_axe      bool;
-    usable_with_two_handed_axe      bool;
-    usable_with_dual_swords         bool;
-    usable_with_spear               bool;
-    usable_with_one_handed_mace     bool;
-    usable_with_two_handed_mace     bool;
-    usable_with_reverse_sword       bool;
-    usable_with_dagger              bool;
-    usable_with_javelin             bool;
-    usable_with_staff               bool;
-    usable_with_bow                 bool;
-    usable_with_crossbow            bool;
-    usable_with_fist_weapon         bool;
-    usable_with_shield              bool;
+    usable_with                     WeaponMask;
     sp_cost                         u16;
     mp_cost                         u16;
     cast_time                       u8;
     cooldown_duration               u16;
     attack_distance                 u8;
-    state_type                      u8;
+    state_type                      StateType;
     element                         ElementType;
     disable                         u16;
     prerequisite_skill              u16;
@@ -160,37 +168,499 @@ pub enum AttackType {
     Physical,
     Shooting,
     Magic,
+    Unknown(u8),
 }
 
-impl Deserialize for SkillData {
-    type Error = std::io::Error;
+#[derive(Default, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum StateType {
+    #[default]
+    None,
+    Stun,
+    Sleep,
+    Silence,
+    Darkness,
+    Immobilize,
+    Slow,
+    Unknown(u8),
+}
 
-    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
+/// A chain of skills whose prerequisites eventually require the first skill again, which makes the
+/// skill tree impossible to learn.
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("skill prerequisite cycle: {}", format_cycle(.cycle))]
+pub struct CycleError {
+    pub cycle: Vec<usize>,
+}
+
+impl NamedValues for TargetType {
+    const VALUES: FieldValues = FieldValues::OneOf(&[
+        "CannotBeCasted",
+        "None",
+        "OnSelf",
+        "Enemy",
+        "Party",
+        "PartyExceptSelf",
+        "AroundCaster",
+        "AroundTarget",
+        "Raid",
+    ]);
+}
+
+impl NamedValues for SkillCategory {
+    const VALUES: FieldValues =
+        FieldValues::OneOf(&["None", "Passive", "Basic", "Combat", "Special"]);
+}
+
+impl NamedValues for DamageType {
+    const VALUES: FieldValues = FieldValues::OneOf(&[
+        "NotUsed",
+        "Fixed",
+        "PlusAdditional",
+        "Coefficient",
+        "CasterCurrentHitpoints",
+        "TargetManaPercent",
+        "TargetHitpointsPercent",
+        "RecCoefficient",
+        "RecPlusAdditional",
+    ]);
+}
+
+impl NamedValues for DamageOverTimeType {
+    const VALUES: FieldValues = FieldValues::OneOf(&["None", "Percent", "Exponential"]);
+}
+
+impl NamedValues for DurationType {
+    const VALUES: FieldValues = FieldValues::OneOf(&[
+        "None",
+        "SecondsAndDisappearOnDeath",
+        "HoursAndPersistsOnDeath",
+        "SecondsAndPersistsOnDeath",
+    ]);
+}
+
+impl NamedValues for AttackType {
+    const VALUES: FieldValues = FieldValues::OneOf(&["Passive", "Physical", "Shooting", "Magic"]);
+}
+
+impl NamedValues for StateType {
+    const VALUES: FieldValues = FieldValues::OneOf(&[
+        "None",
+        "Stun",
+        "Sleep",
+        "Silence",
+        "Darkness",
+        "Immobilize",
+        "Slow",
+    ]);
+}
+
+/// Formats a prerequisite cycle as a chain of skill ids, ending back at the first skill.
+///
+/// # Arguments
+/// * `cycle`   - The skill ids in the cycle.
+fn format_cycle(cycle: &[usize]) -> String {
+    cycle
+        .iter()
+        .chain(cycle.first())
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// The highest skill id that [SkillData::deserialize_with_key] will accept from a file. Retail
+/// skill tables contain a few thousand skills at most, so a count beyond this is almost certainly
+/// a corrupt or truncated file, and is rejected before any records are read.
+pub const DEFAULT_MAX_SKILL_ID: usize = 100_000;
+
+impl Default for SkillDataConfig {
+    fn default() -> Self {
+        Self {
+            ranks_per_skill: None,
+            max_skill_id: DEFAULT_MAX_SKILL_ID,
+        }
+    }
+}
+
+impl SkillDataConfig {
+    /// Gets the number of ranks every skill has for a given [GameVersion].
+    ///
+    /// # Arguments
+    /// * `version` - The game version.
+    pub fn ranks_per_skill(&self, version: GameVersion) -> usize {
+        self.ranks_per_skill
+            .unwrap_or_else(|| ranks_per_skill_for_version(version))
+    }
+}
+
+impl SkillData {
+    /// Gets the [GameVersion] these skills were decoded with. This will return `None` if the
+    /// skills were not decoded from an `.SData` file.
+    pub fn version(&self) -> Option<GameVersion> {
+        self.decoded_version
+    }
+
+    /// Gets the records, grouped by skill id.
+    pub fn records(&self) -> &BTreeMap<usize, Vec<SkillRecord>> {
+        &self.records
+    }
+
+    /// Gets mutable access to the records, grouped by skill id. This marks the skills as modified, so
+    /// they will be re-encoded when serialized rather than written back verbatim.
+    pub fn records_mut(&mut self) -> &mut BTreeMap<usize, Vec<SkillRecord>> {
+        self.dirty = true;
+        &mut self.records
+    }
+
+    /// Gets the configuration used to encode these skills. This is the configuration they were
+    /// decoded with, or the default configuration if they weren't decoded from an `.SData` file.
+    pub fn config(&self) -> SkillDataConfig {
+        self.config
+    }
+
+    /// Sets the configuration used to encode these skills, such as to convert them for a server
+    /// with a different number of ranks per skill. This marks the skills as modified.
+    ///
+    /// # Arguments
+    /// * `config`  - The configuration.
+    pub fn set_config(&mut self, config: SkillDataConfig) {
+        self.dirty = true;
+        self.config = config;
+    }
+
+    /// Iterates over every record, ordered by skill id and rank, without cloning them.
+    pub fn values(&self) -> impl Iterator<Item = &SkillRecord> {
+        self.records.values().flatten()
+    }
+
+    /// Iterates over every record, ordered by skill id and rank, allowing them to be edited in place. This
+    /// marks the skills as modified, like [Self::records_mut].
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut SkillRecord> {
+        self.records_mut().values_mut().flatten()
+    }
+
+    /// Checks if the skills have been modified since they were decoded. SkillData which wasn't decoded
+    /// from an `.SData` file is always considered modified.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.original.is_none()
+    }
+
+    /// Gets the bytes these skills were decoded from, if they haven't been modified since and are
+    /// being encoded for the same [GameVersion]. Unmodified skills are written back exactly as they
+    /// were read, which avoids re-encoding them and keeps any encryption intact.
+    ///
+    /// # Arguments
+    /// * `version` - The game version being encoded for.
+    fn unmodified_bytes(&self, version: GameVersion) -> Option<&[u8]> {
+        match &self.original {
+            Some(original) if !self.dirty && self.decoded_version == Some(version) => {
+                Some(original)
+            }
+            _ => None,
+        }
+    }
+
+    /// Merges another set of skills over these ones. Each record in `overlay` replaces the record
+    /// with the same skill id and rank, and any records which don't exist yet are added. Records
+    /// without a name in `overlay` are treated as unused placeholders, and don't replace anything.
+    ///
+    /// # Arguments
+    /// * `overlay` - The skills to merge over these ones.
+    pub fn merge(&mut self, overlay: SkillData) {
+        merge_records(self.records_mut(), overlay.records, |record| {
+            record.name.is_empty()
+        });
+    }
+
+    /// Gets the skill that must be learned before a given skill. This is taken from the first rank
+    /// which has a prerequisite set, and will return `None` if the skill doesn't exist or has no
+    /// prerequisite.
+    ///
+    /// # Arguments
+    /// * `skill_id`    - The id of the skill.
+    pub fn prerequisites_of(&self, skill_id: usize) -> Option<usize> {
+        self.records
+            .get(&skill_id)?
+            .iter()
+            .map(|record| record.prerequisite_skill as usize)
+            .find(|&prerequisite| prerequisite != 0)
+    }
+
+    /// Gets the ids of every skill which requires a given skill as its prerequisite, in ascending
+    /// order.
+    ///
+    /// # Arguments
+    /// * `skill_id`    - The id of the prerequisite skill.
+    pub fn dependents_of(&self, skill_id: usize) -> Vec<usize> {
+        self.records
+            .keys()
+            .copied()
+            .filter(|&id| self.prerequisites_of(id) == Some(skill_id))
+            .collect()
+    }
+
+    /// Orders every skill so that it appears after its prerequisite. Prerequisites which refer to a
+    /// skill that doesn't exist are ignored. Fails with the offending chain of skills if any
+    /// prerequisites form a cycle.
+    pub fn topo_order(&self) -> Result<Vec<usize>, CycleError> {
+        let mut ordered = Vec::with_capacity(self.records.len());
+        let mut placed = BTreeSet::new();
+
+        for &skill_id in self.records.keys() {
+            // Each skill has at most one prerequisite, so the unplaced part of its dependency
+            // chain is a straight line which we can follow until we reach a placed skill.
+            let mut chain = Vec::new();
+            let mut current = Some(skill_id);
+            while let Some(id) =
+                current.filter(|id| self.records.contains_key(id) && !placed.contains(id))
+            {
+                if let Some(start) = chain.iter().position(|&visited| visited == id) {
+                    return Err(CycleError {
+                        cycle: chain.split_off(start),
+                    });
+                }
+
+                chain.push(id);
+                current = self.prerequisites_of(id);
+            }
+
+            for id in chain.into_iter().rev() {
+                placed.insert(id);
+                ordered.push(id);
+            }
+        }
+
+        Ok(ordered)
+    }
+
+    /// Reads the skills from an `.SData` file which was encrypted with a given SEED key. This is
+    /// required for clients which don't use [SHAIYA_SEED_KEY].
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `version` - The game version.
+    /// * `key`     - The SEED key.
+    pub fn deserialize_with_key<T>(
+        src: &mut T,
+        version: GameVersion,
+        key: &[u32; 32],
+    ) -> Result<Self, std::io::Error>
+    where
+        T: Read + ReadBytesExt,
+    {
+        Self::deserialize_sdata(src, version, key, false)
+    }
+
+    /// Reads the skills from an `.SData` file like [Self::deserialize_with_key], but fails with
+    /// [TrailingBytes](crate::io::TrailingBytes) if the records don't take up the whole decrypted
+    /// payload. This catches a record layout which doesn't match the file, rather than silently
+    /// reading the wrong fields.
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `version` - The game version.
+    /// * `key`     - The SEED key.
+    pub fn deserialize_strict<T>(
+        src: &mut T,
+        version: GameVersion,
+        key: &[u32; 32],
+    ) -> Result<Self, std::io::Error>
+    where
+        T: Read + ReadBytesExt,
+    {
+        Self::deserialize_sdata(src, version, key, true)
+    }
+
+    /// Reads the skills from an `.SData` file which was encrypted with a given SEED key, rejecting
+    /// the file if it declares more skill ids than a given maximum.
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `version` - The game version.
+    /// * `key`     - The SEED key.
+    /// * `limit`   - The highest skill id to accept.
+    pub fn deserialize_with_max_skill_id<T>(
+        src: &mut T,
+        version: GameVersion,
+        key: &[u32; 32],
+        limit: usize,
+    ) -> Result<Self, std::io::Error>
+    where
+        T: Read + ReadBytesExt,
+    {
+        let config = SkillDataConfig {
+            max_skill_id: limit,
+            ..Default::default()
+        };
+        Self::deserialize_with_config(src, version, key, config)
+    }
+
+    /// Reads the skills from an `.SData` file which was encrypted with a given SEED key, using a
+    /// custom configuration rather than the retail limits. The configuration is retained, and is
+    /// used when the skills are serialized.
+    ///
+    /// # Arguments
+    /// * `src`     - The source buffer.
+    /// * `version` - The game version.
+    /// * `key`     - The SEED key.
+    /// * `config`  - The configuration.
+    pub fn deserialize_with_config<T>(
+        src: &mut T,
+        version: GameVersion,
+        key: &[u32; 32],
+        config: SkillDataConfig,
+    ) -> Result<Self, std::io::Error>
     where
         T: Read + ReadBytesExt,
-        Self: Sized,
     {
-        let mut decrypted = SData::deserialize(src)?;
-        let mut src = Cursor::new(&mut decrypted.data);
+        Self::deserialize_sdata_with(src, key, false, |src| Self::parse(src, version, config))
+    }
 
+    /// Parses the skills from the decrypted payload of an `.SData` file.
+    ///
+    /// # Arguments
+    /// * `src`     - The cursor over the decrypted payload.
+    /// * `version` - The game version.
+    /// * `config`  - The configuration.
+    fn parse(
+        src: &mut Cursor<&[u8]>,
+        version: GameVersion,
+        config: SkillDataConfig,
+    ) -> Result<Self, std::io::Error> {
         let max_skill_id = src.read_u32::<LE>()? as usize;
-        let ranks_per_skill = ranks_per_skill_for_version(version);
+        let limit = config.max_skill_id;
+        if max_skill_id > limit {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("skill id count {max_skill_id} exceeds the maximum of {limit}"),
+            ));
+        }
+        let ranks_per_skill = config.ranks_per_skill(version);
 
         let mut map = BTreeMap::new();
         for skill_id in 1..=max_skill_id {
-            for _rank in 1..=ranks_per_skill {
-                let record = SkillRecord::versioned_deserialize(&mut src, version)?;
-                // println!("{:?}", record);
+            for rank in 1..=ranks_per_skill {
+                let record = SkillRecord::versioned_deserialize(src, version).map_err(|err| {
+                    std::io::Error::new(
+                        err.kind(),
+                        format!("failed parsing skill {skill_id} rank {rank}: {err}"),
+                    )
+                })?;
                 map.entry(skill_id)
                     .or_insert_with(|| Vec::with_capacity(ranks_per_skill))
                     .push(record);
             }
         }
 
-        Ok(Self(map))
+        Ok(Self {
+            records: map,
+            decoded_version: Some(version),
+            original: None,
+            dirty: false,
+            config,
+        })
+    }
+}
+
+impl SdataBacked for SkillData {
+    fn from_decrypted(
+        src: &mut Cursor<&[u8]>,
+        version: GameVersion,
+    ) -> Result<Self, std::io::Error> {
+        Self::parse(src, version, SkillDataConfig::default())
+    }
+
+    fn retain_original(&mut self, original: Vec<u8>) {
+        self.original = Some(original);
+    }
+}
+
+impl SerializedLen for SkillData {
+    fn serialized_len(&self, version: GameVersion) -> usize {
+        if let Some(original) = self.unmodified_bytes(version) {
+            return original.len();
+        }
+
+        // Every skill id up to the max is written with exactly one record per rank, with default
+        // records filling any gaps and any extra ranks being dropped.
+        let max_skill_id = self.records.keys().max().copied().unwrap_or_default();
+        let ranks_per_skill = self.config.ranks_per_skill(version);
+        let default_len = SkillRecord::default().serialized_len(version);
+
+        let mut len = size_of::<u32>() + max_skill_id * ranks_per_skill * default_len;
+        for records in self.records.range(1..).map(|(_, records)| records) {
+            for record in records.iter().take(ranks_per_skill) {
+                len = len + record.serialized_len(version) - default_len;
+            }
+        }
+        len
+    }
+}
+
+impl From<BTreeMap<usize, Vec<SkillRecord>>> for SkillData {
+    fn from(records: BTreeMap<usize, Vec<SkillRecord>>) -> Self {
+        Self {
+            records,
+            ..Default::default()
+        }
     }
 }
 
+impl Deserialize for SkillData {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        Self::deserialize_with_key(src, version, SHAIYA_SEED_KEY)
+    }
+
+    fn detect_version(src: &[u8]) -> Option<GameVersion> {
+        let decrypted = SData::deserialize(&mut Cursor::new(src)).ok()?;
+        GameVersion::iter().find(|version| sample_lines_up(&decrypted.data, *version))
+    }
+}
+
+/// The number of skills decoded from the start of a skill table when detecting its version.
+const DETECTION_SAMPLE_SIZE: usize = 4;
+
+/// Checks if the first skills of a decrypted skill table decode cleanly with the layout of a given
+/// version. Every version has a different number of ranks per skill, and every rank with a name
+/// carries its own rank number, which a record layout that doesn't match the file is very unlikely
+/// to reproduce. Placeholder ranks without a name prove nothing, so at least one named rank must
+/// be sampled for the version to be accepted.
+///
+/// # Arguments
+/// * `data`    - The decrypted skill table.
+/// * `version` - The game version.
+fn sample_lines_up(data: &[u8], version: GameVersion) -> bool {
+    let mut src = Cursor::new(data);
+    let Ok(max_skill_id) = src.read_u32::<LE>() else {
+        return false;
+    };
+
+    let ranks_per_skill = ranks_per_skill_for_version(version);
+    let mut named = 0;
+    for _ in 0..(max_skill_id as usize).min(DETECTION_SAMPLE_SIZE) {
+        for rank in 1..=ranks_per_skill {
+            let Ok(record) = SkillRecord::versioned_deserialize(&mut src, version) else {
+                return false;
+            };
+            if !record.name.is_empty() {
+                if record.rank as usize != rank {
+                    return false;
+                }
+                named += 1;
+            }
+        }
+    }
+
+    // The whole table fit within the sample, so it should have been consumed exactly.
+    named > 0
+        && (max_skill_id as usize > DETECTION_SAMPLE_SIZE || src.position() as usize == data.len())
+}
+
 impl Serialize for SkillData {
     type Error = std::io::Error;
 
@@ -198,17 +668,24 @@ impl Serialize for SkillData {
     where
         T: Write + WriteBytesExt,
     {
-        let max_skill_id = *self.0.keys().max().unwrap();
-        dst.write_u32::<LE>(max_skill_id as u32)?;
+        if let Some(original) = self.unmodified_bytes(version) {
+            return dst.write_all(original);
+        }
+
+        let max_skill_id = self.records.keys().max().copied().unwrap_or_default();
+        dst.write_u32::<LE>(checked_count(max_skill_id, "skill id")?)?;
 
         let mut default_record = SkillRecord::default();
-        let ranks_per_skill = ranks_per_skill_for_version(version);
+        let ranks_per_skill = self.config.ranks_per_skill(version);
 
+        // Skill ids missing from the map are written as a full block of default records, as the
+        // client expects every id up to the max to be present.
         for skill_id in 1..=max_skill_id {
             let records = self
-                .0
+                .records
                 .get(&skill_id)
-                .expect("failed to get record for skill id");
+                .map(Vec::as_slice)
+                .unwrap_or_default();
 
             for rank in 1..=ranks_per_skill {
                 if rank > records.len() {
@@ -223,6 +700,40 @@ impl Serialize for SkillData {
     }
 }
 
+impl From<u8> for TargetType {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => Self::CannotBeCasted,
+            1 => Self::None,
+            2 => Self::OnSelf,
+            3 => Self::Enemy,
+            4 => Self::Party,
+            5 => Self::PartyExceptSelf,
+            6 => Self::AroundCaster,
+            7 => Self::AroundTarget,
+            8 => Self::Raid,
+            _ => Self::Unknown(id),
+        }
+    }
+}
+
+impl From<&TargetType> for u8 {
+    fn from(target_type: &TargetType) -> Self {
+        match *target_type {
+            TargetType::CannotBeCasted => 0,
+            TargetType::None => 1,
+            TargetType::OnSelf => 2,
+            TargetType::Enemy => 3,
+            TargetType::Party => 4,
+            TargetType::PartyExceptSelf => 5,
+            TargetType::AroundCaster => 6,
+            TargetType::AroundTarget => 7,
+            TargetType::Raid => 8,
+            TargetType::Unknown(id) => id,
+        }
+    }
+}
+
 impl Deserialize for TargetType {
     type Error = std::io::Error;
 
@@ -231,19 +742,7 @@ impl Deserialize for TargetType {
         T: Read + ReadBytesExt,
         Self: Sized,
     {
-        let target_type = src.read_u8()?;
-        match target_type {
-            0 => Ok(Self::CannotBeCasted),
-            1 => Ok(Self::None),
-            2 => Ok(Self::OnSelf),
-            3 => Ok(Self::Enemy),
-            4 => Ok(Self::Party),
-            5 => Ok(Self::PartyExceptSelf),
-            6 => Ok(Self::AroundCaster),
-            7 => Ok(Self::AroundTarget),
-            8 => Ok(Self::Raid),
-            _ => Ok(Self::Unknown(target_type)),
-        }
+        Ok(Self::from(src.read_u8()?))
     }
 }
 
@@ -254,19 +753,7 @@ impl Serialize for TargetType {
     where
         T: Write + WriteBytesExt,
     {
-        let id = match *self {
-            Self::CannotBeCasted => 0,
-            Self::None => 1,
-            Self::OnSelf => 2,
-            Self::Enemy => 3,
-            Self::Party => 4,
-            Self::PartyExceptSelf => 5,
-            Self::AroundCaster => 6,
-            Self::AroundTarget => 7,
-            Self::Raid => 8,
-            Self::Unknown(id) => id,
-        };
-        dst.write_u8(id)
+        dst.write_u8(u8::from(self))
     }
 }
 
@@ -432,6 +919,30 @@ impl Serialize for DurationType {
     }
 }
 
+impl From<u8> for AttackType {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => Self::Passive,
+            1 => Self::Physical,
+            2 => Self::Shooting,
+            3 => Self::Magic,
+            _ => Self::Unknown(id),
+        }
+    }
+}
+
+impl From<&AttackType> for u8 {
+    fn from(attack_type: &AttackType) -> Self {
+        match *attack_type {
+            AttackType::Passive => 0,
+            AttackType::Physical => 1,
+            AttackType::Shooting => 2,
+            AttackType::Magic => 3,
+            AttackType::Unknown(id) => id,
+        }
+    }
+}
+
 impl Deserialize for AttackType {
     type Error = std::io::Error;
 
@@ -440,17 +951,7 @@ impl Deserialize for AttackType {
         T: Read + ReadBytesExt,
         Self: Sized,
     {
-        let attack_type = src.read_u8()?;
-        match attack_type {
-            0 => Ok(Self::Passive),
-            1 => Ok(Self::Physical),
-            2 => Ok(Self::Shooting),
-            3 => Ok(Self::Magic),
-            _ => Err(std::io::Error::new(
-                ErrorKind::InvalidInput,
-                format!("invalid attack type {attack_type}"),
-            )),
-        }
+        Ok(Self::from(src.read_u8()?))
     }
 }
 
@@ -461,18 +962,65 @@ impl Serialize for AttackType {
     where
         T: Write + WriteBytesExt,
     {
-        let id = match *self {
-            Self::Passive => 0,
-            Self::Physical => 1,
-            Self::Shooting => 2,
-            Self::Magic => 3,
-        };
-        dst.write_u8(id)
+        dst.write_u8(u8::from(self))
+    }
+}
+
+impl From<u8> for StateType {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => Self::None,
+            1 => Self::Stun,
+            2 => Self::Sleep,
+            3 => Self::Silence,
+            4 => Self::Darkness,
+            5 => Self::Immobilize,
+            6 => Self::Slow,
+            _ => Self::Unknown(id),
+        }
+    }
+}
+
+impl From<&StateType> for u8 {
+    fn from(state_type: &StateType) -> Self {
+        match *state_type {
+            StateType::None => 0,
+            StateType::Stun => 1,
+            StateType::Sleep => 2,
+            StateType::Silence => 3,
+            StateType::Darkness => 4,
+            StateType::Immobilize => 5,
+            StateType::Slow => 6,
+            StateType::Unknown(id) => id,
+        }
+    }
+}
+
+impl Deserialize for StateType {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        Ok(Self::from(src.read_u8()?))
+    }
+}
+
+impl Serialize for StateType {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        dst.write_u8(u8::from(self))
     }
 }
 
 fn max_ability_types_for_version(version: GameVersion) -> usize {
-    if version >= GameVersion::Ep6 {
+    if version.at_least(GameVersion::Ep6) {
         10
     } else {
         3
@@ -486,3 +1034,248 @@ fn ranks_per_skill_for_version(version: GameVersion) -> usize {
         GameVersion::Ep6 | GameVersion::Ep6v2 => 15,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AbilityRecord, AttackType, ClassMask, CycleError, SkillData, SkillDataConfig, SkillRecord,
+        StateType, TargetType, WeaponMask, SHAIYA_SEED_KEY,
+    };
+    use crate::fs::types::sdata::assert_sdata_roundtrip;
+    use crate::io::{Deserialize, GameVersion, Serialize, SerializedLen};
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    #[test]
+    fn empty_round_trip() {
+        let mut dst = Vec::new();
+        SkillData::default()
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        assert_eq!(dst, [0, 0, 0, 0]);
+
+        let mut src = Cursor::new(dst.as_slice());
+        let skills = SkillData::versioned_deserialize(&mut src, GameVersion::Ep6).unwrap();
+        assert!(skills.records.is_empty());
+    }
+
+    #[test]
+    fn sparse_skill_ids() {
+        let mut map = BTreeMap::new();
+        for skill_id in [1, 2, 5] {
+            let record = SkillRecord {
+                name: format!("skill {skill_id}"),
+                rank: 1,
+                ..Default::default()
+            };
+            map.insert(skill_id, vec![record]);
+        }
+
+        let mut dst = Vec::new();
+        SkillData::from(map)
+            .versioned_serialize(&mut dst, GameVersion::Ep4)
+            .unwrap();
+
+        let mut src = Cursor::new(dst.as_slice());
+        let skills = SkillData::versioned_deserialize(&mut src, GameVersion::Ep4).unwrap();
+        assert_eq!(
+            skills.records.keys().copied().collect::<Vec<_>>(),
+            [1, 2, 3, 4, 5]
+        );
+        assert_eq!(skills.records[&5][0].name, "skill 5");
+        assert!(skills.records[&3]
+            .iter()
+            .all(|record| record.name.is_empty()));
+        assert_eq!(skills.records[&4][2].rank, 3);
+    }
+
+    #[test]
+    fn skill_record_round_trip() {
+        let record = SkillRecord {
+            name: "Stun Slash".to_owned(),
+            rank: 2,
+            effect: 4,
+            usable_by: ClassMask::FIGHTER,
+            usable_with: WeaponMask::ONE_HANDED_SWORD | WeaponMask::SHIELD,
+            attack_type: AttackType::Physical,
+            target_type: TargetType::Enemy,
+            ability_effects: vec![AbilityRecord {
+                ability_type: 1,
+                ability_value: 25,
+            }],
+            change_value: 9,
+            ..Default::default()
+        };
+
+        for version in GameVersion::all() {
+            let mut bytes = Vec::new();
+            record.versioned_serialize(&mut bytes, *version).unwrap();
+            assert_sdata_roundtrip!(SkillRecord, *version, bytes);
+        }
+    }
+
+    #[test]
+    fn typed_enum_round_trip() {
+        for id in 0..=u8::MAX {
+            assert_eq!(u8::from(&TargetType::from(id)), id);
+            assert_eq!(u8::from(&AttackType::from(id)), id);
+            assert_eq!(u8::from(&StateType::from(id)), id);
+        }
+
+        assert_eq!(AttackType::from(200), AttackType::Unknown(200));
+        assert_eq!(TargetType::from(3), TargetType::Enemy);
+    }
+
+    /// Creates a set of single-rank skills from a list of `(skill id, prerequisite)` pairs.
+    fn skill_tree(prerequisites: &[(usize, u16)]) -> SkillData {
+        let map: BTreeMap<_, _> = prerequisites
+            .iter()
+            .map(|&(skill_id, prerequisite_skill)| {
+                let record = SkillRecord {
+                    prerequisite_skill,
+                    ..Default::default()
+                };
+                (skill_id, vec![record])
+            })
+            .collect();
+        SkillData::from(map)
+    }
+
+    #[test]
+    fn prerequisite_graph() {
+        let skills = skill_tree(&[(1, 3), (2, 0), (3, 2), (4, 2), (5, 99)]);
+        assert_eq!(skills.prerequisites_of(1), Some(3));
+        assert_eq!(skills.prerequisites_of(2), None);
+        assert_eq!(skills.prerequisites_of(6), None);
+        assert_eq!(skills.dependents_of(2), [3, 4]);
+        assert!(skills.dependents_of(1).is_empty());
+        assert_eq!(skills.topo_order(), Ok(vec![2, 3, 1, 4, 5]));
+    }
+
+    #[test]
+    fn prerequisite_cycle() {
+        let skills = skill_tree(&[(1, 0), (2, 3), (3, 2), (4, 2)]);
+        let err = skills.topo_order().unwrap_err();
+        assert_eq!(err, CycleError { cycle: vec![2, 3] });
+        assert_eq!(err.to_string(), "skill prerequisite cycle: 2 -> 3 -> 2");
+
+        let skills = skill_tree(&[(7, 7)]);
+        assert_eq!(skills.topo_order(), Err(CycleError { cycle: vec![7] }));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn oversized_id_is_rejected() {
+        let map = BTreeMap::from([(u32::MAX as usize + 1, vec![SkillRecord::default()])]);
+
+        let mut dst = Vec::new();
+        let err = SkillData::from(map)
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn serialized_len_matches_output() {
+        let skill = |name: &str| SkillRecord {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let extra_ranks = (0..20).map(|rank| skill(&format!("rank {rank}"))).collect();
+        let skills = SkillData::from(BTreeMap::from([
+            (2, vec![skill("Slash"), skill("Bash")]),
+            (4, extra_ranks),
+        ]));
+
+        for version in GameVersion::iter() {
+            let mut dst = Vec::new();
+            skills.versioned_serialize(&mut dst, version).unwrap();
+            assert_eq!(skills.serialized_len(version), dst.len(), "{version}");
+        }
+    }
+
+    #[test]
+    fn absurd_skill_id_count_is_rejected() {
+        let mut src = Cursor::new(u32::MAX.to_le_bytes());
+        let err = SkillData::versioned_deserialize(&mut src, GameVersion::Ep5).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut dst = Vec::new();
+        skill_tree(&[(1, 0), (2, 1), (3, 2)])
+            .versioned_serialize(&mut dst, GameVersion::Ep5)
+            .unwrap();
+        let mut src = Cursor::new(dst.as_slice());
+        let err = SkillData::deserialize_with_max_skill_id(
+            &mut src,
+            GameVersion::Ep5,
+            SHAIYA_SEED_KEY,
+            2,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn custom_rank_count() {
+        let records = (1..=20)
+            .map(|rank| SkillRecord {
+                name: "Slash".to_owned(),
+                rank,
+                ..Default::default()
+            })
+            .collect();
+        let config = SkillDataConfig {
+            ranks_per_skill: Some(20),
+            ..Default::default()
+        };
+        let mut skills = SkillData::from(BTreeMap::from([(1, records)]));
+        skills.set_config(config);
+
+        let mut dst = Vec::new();
+        skills
+            .versioned_serialize(&mut dst, GameVersion::Ep6)
+            .unwrap();
+        assert_eq!(dst.len(), skills.serialized_len(GameVersion::Ep6));
+
+        let read = SkillData::deserialize_with_config(
+            &mut dst.as_slice(),
+            GameVersion::Ep6,
+            SHAIYA_SEED_KEY,
+            config,
+        )
+        .unwrap();
+        assert_eq!(read.config(), config);
+        assert_eq!(read.records()[&1].len(), 20);
+        assert_eq!(read.records()[&1][19].rank, 20);
+
+        // The retail layout has fewer ranks, so the extra records are left over.
+        assert!(SkillData::deserialize_strict(
+            &mut dst.as_slice(),
+            GameVersion::Ep6,
+            SHAIYA_SEED_KEY
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn values_cover_every_rank() {
+        let ranks = |count: u8| {
+            (1..=count)
+                .map(|rank| SkillRecord {
+                    rank,
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut skills = SkillData::from(BTreeMap::from([(1, ranks(3)), (4, ranks(5))]));
+
+        let total = skills.records().values().map(Vec::len).sum::<usize>();
+        assert_eq!(skills.values().count(), total);
+
+        for skill in skills.values_mut() {
+            skill.rank += 1;
+        }
+        assert_eq!(skills.values().map(|skill| skill.rank).max(), Some(6));
+    }
+}
@@ -1,4 +1,6 @@
-use crate::io::{Deserialize, GameVersion, ShaiyaReadExt};
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt};
+use byteorder::{WriteBytesExt, BE, LE};
+use std::io::Write;
 
 /// The prefix that will appear at the beginning of a text file if it is encoded with UTF-16, little endian.
 /// https://learn.microsoft.com/en-us/windows/win32/intl/using-byte-order-marks
@@ -8,6 +10,11 @@ const UTF16_LE: u16 = 0xFFFE;
 /// https://learn.microsoft.com/en-us/windows/win32/intl/using-byte-order-marks
 const UTF16_BE: u16 = 0xFEFF;
 
+/// The prefix that will appear at the beginning of a text file if it is encoded with UTF-8, and was
+/// saved with a byte order mark.
+/// https://learn.microsoft.com/en-us/windows/win32/intl/using-byte-order-marks
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
 impl Deserialize for String {
     type Error = std::io::Error;
 
@@ -17,38 +24,159 @@ impl Deserialize for String {
         Self: Sized,
     {
         let bytes = src.consume_all();
-        let byte_order_mark = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if let Some(utf8) = bytes.strip_prefix(UTF8_BOM) {
+            return Ok(String::from_utf8_lossy(utf8).into_owned());
+        }
 
-        return match byte_order_mark {
-            UTF16_BE => Ok(parse_string_utf16_be(&bytes[2..])),
-            UTF16_LE => Ok(parse_string_utf16_le(&bytes[2..])),
-            _ => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        let byte_order_mark = match bytes.as_slice() {
+            [first, second, ..] => u16::from_be_bytes([*first, *second]),
+            _ => 0,
         };
+
+        match byte_order_mark {
+            UTF16_BE => parse_string_utf16_be(&bytes[2..]),
+            UTF16_LE => parse_string_utf16_le(&bytes[2..]),
+            _ => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        }
     }
 }
 
-/// Parses a String encoded with UTF-16-BE from an input.
+impl Serialize for String {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        serialize_utf16_le(self, dst)
+    }
+}
+
+/// Writes a string encoded with UTF-16-LE, prefixed with the matching byte order mark. This is
+/// the encoding used by the client for localized text files.
+///
+/// # Arguments
+/// * `text`    - The text to write.
+/// * `dst`     - The destination buffer.
+pub fn serialize_utf16_le<T>(text: &str, dst: &mut T) -> Result<(), std::io::Error>
+where
+    T: Write + WriteBytesExt,
+{
+    dst.write_u16::<BE>(UTF16_LE)?;
+    for codepoint in text.encode_utf16() {
+        dst.write_u16::<LE>(codepoint)?;
+    }
+    Ok(())
+}
+
+/// Writes a string encoded with UTF-8, without a byte order mark.
+///
+/// # Arguments
+/// * `text`    - The text to write.
+/// * `dst`     - The destination buffer.
+pub fn serialize_utf8<T>(text: &str, dst: &mut T) -> Result<(), std::io::Error>
+where
+    T: Write + WriteBytesExt,
+{
+    dst.write_all(text.as_bytes())
+}
+
+/// Parses a String encoded with UTF-16-BE from an input. Invalid code units are replaced with
+/// the replacement character, but an odd number of bytes is an error, as the final code unit has
+/// been truncated.
 ///
 /// # Arguments
 /// * `src` - The source.
-fn parse_string_utf16_be(src: &[u8]) -> String {
+fn parse_string_utf16_be(src: &[u8]) -> Result<String, std::io::Error> {
+    ensure_whole_code_units(src)?;
     let codepoints = src
         .chunks_exact(2)
         .map(|a| u16::from_be_bytes([a[0], a[1]]))
         .collect::<Vec<u16>>();
 
-    String::from_utf16_lossy(&codepoints)
+    Ok(String::from_utf16_lossy(&codepoints))
 }
 
-/// Parses a String encoded with UTF-16-LE from an input.
+/// Parses a String encoded with UTF-16-LE from an input. Invalid code units are replaced with
+/// the replacement character, but an odd number of bytes is an error, as the final code unit has
+/// been truncated.
 ///
 /// # Arguments
 /// * `src` - The source.
-fn parse_string_utf16_le(src: &[u8]) -> String {
+fn parse_string_utf16_le(src: &[u8]) -> Result<String, std::io::Error> {
+    ensure_whole_code_units(src)?;
     let codepoints = src
         .chunks_exact(2)
         .map(|a| u16::from_le_bytes([a[0], a[1]]))
         .collect::<Vec<u16>>();
 
-    String::from_utf16_lossy(&codepoints)
+    Ok(String::from_utf16_lossy(&codepoints))
+}
+
+/// Checks that a UTF-16 input is made up of whole code units, rather than ending with a single
+/// byte of a truncated one.
+///
+/// # Arguments
+/// * `src` - The source.
+fn ensure_whole_code_units(src: &[u8]) -> Result<(), std::io::Error> {
+    match src.len() % 2 {
+        0 => Ok(()),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "UTF-16 text has an odd length of {} bytes, so its final code unit is truncated",
+                src.len()
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::types::serialize_utf8;
+    use crate::io::{Deserialize, Serialize};
+    use std::io::Cursor;
+
+    #[test]
+    fn strips_utf8_bom() {
+        let bytes = [0xEF, 0xBB, 0xBF, b'k', b'e', b'y'];
+        let text = String::deserialize(&mut Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(text.chars().next(), Some('k'));
+        assert_eq!(text, "key");
+    }
+
+    #[test]
+    fn utf16_le_round_trip() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for codepoint in "Épée\r\n한국어".encode_utf16() {
+            bytes.extend_from_slice(&codepoint.to_le_bytes());
+        }
+
+        let text = String::deserialize(&mut Cursor::new(bytes.as_slice())).unwrap();
+        let mut dst = Vec::new();
+        text.serialize(&mut dst).unwrap();
+        assert_eq!(dst, bytes);
+
+        let reread = String::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(reread, text);
+    }
+
+    #[test]
+    fn utf8_round_trip() {
+        let mut dst = Vec::new();
+        serialize_utf8("filter", &mut dst).unwrap();
+
+        let text = String::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(text, "filter");
+    }
+
+    #[test]
+    fn odd_length_utf16_is_rejected() {
+        let bytes = [0xFF, 0xFE, b'k', 0, b'e', 0, b'y'];
+        let err = String::deserialize(&mut Cursor::new(&bytes[..])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let bytes = [0xFE, 0xFF, 0, b'k', 0];
+        assert!(String::deserialize(&mut Cursor::new(&bytes[..])).is_err());
+    }
 }
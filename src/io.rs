@@ -1,4 +1,7 @@
 use byteorder::WriteBytesExt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
 
 #[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Copy, Clone)]
 pub enum GameVersion {
@@ -16,6 +19,96 @@ impl GameVersion {
     pub fn last<'a>() -> &'a Self {
         &Self::Ep6v2
     }
+
+    /// Iterates over every version by value, in the same newest-first order as [Self::all].
+    pub fn iter() -> impl Iterator<Item = Self> + Clone {
+        Self::all().iter().copied()
+    }
+
+    /// Checks if this version is newer than another version.
+    ///
+    /// # Arguments
+    /// * `other`   - The version to compare against.
+    pub fn newer_than(self, other: Self) -> bool {
+        self > other
+    }
+
+    /// Checks if this version is older than another version.
+    ///
+    /// # Arguments
+    /// * `other`   - The version to compare against.
+    pub fn older_than(self, other: Self) -> bool {
+        self < other
+    }
+
+    /// Checks if this version is the same as, or newer than, another version.
+    ///
+    /// # Arguments
+    /// * `other`   - The version to compare against.
+    pub fn at_least(self, other: Self) -> bool {
+        !self.older_than(other)
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("unknown game version {0:?} (expected one of ep4, ep5, ep6, ep6v2)")]
+pub struct ParseGameVersionError(String);
+
+impl FromStr for GameVersion {
+    type Err = ParseGameVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ep4" => Ok(Self::Ep4),
+            "ep5" => Ok(Self::Ep5),
+            "ep6" => Ok(Self::Ep6),
+            "ep6v2" => Ok(Self::Ep6v2),
+            _ => Err(ParseGameVersionError(s.to_owned())),
+        }
+    }
+}
+
+impl Display for GameVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::Ep4 => "ep4",
+            Self::Ep5 => "ep5",
+            Self::Ep6 => "ep6",
+            Self::Ep6v2 => "ep6v2",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An error for when a type was decoded without consuming all of its input. Like running out of
+/// input, this usually means the type was decoded with the wrong [GameVersion].
+#[derive(Error, Debug)]
+#[error("{count} trailing bytes were left after decoding")]
+pub struct TrailingBytes {
+    pub count: usize,
+}
+
+impl From<TrailingBytes> for std::io::Error {
+    fn from(err: TrailingBytes) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Checks if an error from decoding a type suggests that the type was decoded with the wrong
+/// [GameVersion], rather than the data being corrupt. Running out of input, leaving
+/// [TrailingBytes], and reading a field value which isn't valid (reported as
+/// [std::io::ErrorKind::InvalidInput]) are all expected when the record layout doesn't match. Any
+/// other error, such as a checksum mismatch, means the data is corrupt regardless of the version.
+///
+/// # Arguments
+/// * `err` - The decoding error.
+pub fn is_version_mismatch(err: &std::io::Error) -> bool {
+    match err.kind() {
+        std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::InvalidInput => true,
+        _ => err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<TrailingBytes>()),
+    }
 }
 
 pub trait Serialize {
@@ -48,23 +141,202 @@ pub trait Deserialize {
     where
         T: std::io::Read + byteorder::ReadBytesExt,
         Self: Sized;
+
+    /// Guesses the [GameVersion] some encoded data was written with from cues in its structure,
+    /// without fully decoding it with every version. Types which can't tell return `None`, which
+    /// is the default.
+    ///
+    /// # Arguments
+    /// * `src` - The encoded data.
+    fn detect_version(_src: &[u8]) -> Option<GameVersion>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// A type which can compute the number of bytes it serializes to, such as to size a buffer or to
+/// check a size limit before writing. By default this serializes to a writer which only counts the
+/// bytes it is given, but types with a simple layout can override it with the arithmetic.
+pub trait SerializedLen: Serialize {
+    /// Gets the number of bytes this value serializes to. If serializing would fail, this is the
+    /// number of bytes which would be written before the failure.
+    ///
+    /// # Arguments
+    /// * `version` - The game version.
+    fn serialized_len(&self, version: GameVersion) -> usize {
+        let mut counter = ByteCounter(0);
+        let _ = self.versioned_serialize(&mut counter, version);
+        counter.0
+    }
+}
+
+/// A writer which discards its input, and only counts the number of bytes written to it.
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes every item in a slice, one after another, without a length prefix.
+///
+/// # Arguments
+/// * `dst`     - The destination buffer.
+/// * `items`   - The items to serialize.
+/// * `version` - The game version.
+pub fn serialize_items<T, I>(dst: &mut T, items: &[I], version: GameVersion) -> Result<(), I::Error>
+where
+    T: std::io::Write + WriteBytesExt,
+    I: Serialize,
+{
+    items
+        .iter()
+        .try_for_each(|item| item.versioned_serialize(dst, version))
+}
+
+/// Deserializes a known number of items, one after another, without a length prefix.
+///
+/// # Arguments
+/// * `src`     - The source buffer.
+/// * `count`   - The number of items to read.
+/// * `version` - The game version.
+pub fn deserialize_items<T, I>(
+    src: &mut T,
+    count: usize,
+    version: GameVersion,
+) -> Result<Vec<I>, I::Error>
+where
+    T: std::io::Read + byteorder::ReadBytesExt,
+    I: Deserialize,
+{
+    // The count usually comes straight from the source, so don't trust it for the allocation.
+    let mut items = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        items.push(I::versioned_deserialize(src, version)?);
+    }
+    Ok(items)
 }
 
+/// A [Vec] is written as a little-endian `u32` count, followed by each of its items.
+impl<I> Serialize for Vec<I>
+where
+    I: Serialize,
+    I::Error: From<std::io::Error>,
+{
+    type Error = I::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: std::io::Write + WriteBytesExt,
+    {
+        dst.write_u32::<byteorder::LittleEndian>(self.len() as u32)?;
+        serialize_items(dst, self, version)
+    }
+}
+
+impl<I> SerializedLen for Vec<I>
+where
+    I: SerializedLen,
+    I::Error: From<std::io::Error>,
+{
+    fn serialized_len(&self, version: GameVersion) -> usize {
+        let items = self.iter().map(|item| item.serialized_len(version));
+        std::mem::size_of::<u32>() + items.sum::<usize>()
+    }
+}
+
+impl<I> Deserialize for Vec<I>
+where
+    I: Deserialize,
+    I::Error: From<std::io::Error>,
+{
+    type Error = I::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: std::io::Read + byteorder::ReadBytesExt,
+        Self: Sized,
+    {
+        let count = src.read_u32::<byteorder::LittleEndian>()? as usize;
+        deserialize_items(src, count, version)
+    }
+}
+
+/// Implements [Serialize] and [Deserialize] for a tuple, which is written as each of its elements
+/// in order. Every element must share the same error type.
+macro_rules! tuple_impls {
+    ($($name:ident),+) => {
+        impl<E, $($name),+> Serialize for ($($name,)+)
+        where
+            $($name: Serialize<Error = E>,)+
+        {
+            type Error = E;
+
+            #[allow(non_snake_case)]
+            fn versioned_serialize<T>(&self, dst: &mut T, version: GameVersion) -> Result<(), Self::Error>
+            where
+                T: std::io::Write + WriteBytesExt,
+            {
+                let ($($name,)+) = self;
+                $($name.versioned_serialize(dst, version)?;)+
+                Ok(())
+            }
+        }
+
+        impl<E, $($name),+> Deserialize for ($($name,)+)
+        where
+            $($name: Deserialize<Error = E>,)+
+        {
+            type Error = E;
+
+            fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
+            where
+                T: std::io::Read + byteorder::ReadBytesExt,
+                Self: Sized,
+            {
+                Ok(($($name::versioned_deserialize(src, version)?,)+))
+            }
+        }
+    };
+}
+
+tuple_impls!(A, B);
+tuple_impls!(A, B, C);
+tuple_impls!(A, B, C, D);
+
 pub trait ShaiyaReadExt {
     type Error;
 
     fn consume_all(&mut self) -> Vec<u8>;
 
+    fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>, Self::Error>;
+
     fn read_string(&mut self, length: usize) -> Result<String, Self::Error>;
 
     fn read_length_prefixed_string(&mut self) -> Result<String, Self::Error>;
 
+    fn read_cstr(&mut self) -> Result<String, Self::Error>;
+
+    fn read_length_prefixed_string_capped(&mut self, max: usize) -> Result<String, Self::Error>;
+
+    fn read_length_prefixed_string_be(&mut self) -> Result<String, Self::Error>;
+
     fn skip(&mut self, length: usize) -> Result<(), Self::Error>;
 }
 
 pub trait ShaiyaWriteExt {
     type Error;
 
+    fn write_fixed(&mut self, bytes: &[u8], length: usize) -> Result<(), Self::Error>;
+
     fn write_string<T>(&mut self, text: T, length: usize) -> Result<(), Self::Error>
     where
         T: AsRef<str>;
@@ -73,27 +345,35 @@ pub trait ShaiyaWriteExt {
     where
         T: AsRef<str>;
 
+    fn write_length_prefixed_string_be<T>(&mut self, text: T) -> Result<(), Self::Error>
+    where
+        T: AsRef<str>;
+
     fn write_bool(&mut self, value: bool) -> Result<(), Self::Error>;
 }
 
 impl<R> ShaiyaReadExt for R
 where
-    R: std::io::Read + byteorder::ReadBytesExt,
+    R: std::io::Read + byteorder::ReadBytesExt + ?Sized,
 {
     type Error = std::io::Error;
 
     fn consume_all(&mut self) -> Vec<u8> {
+        // Like the byte-by-byte read this replaces, an error simply ends the read, and whatever
+        // was read up until that point is returned.
         let mut dst = Vec::new();
-        while let Ok(b) = self.read_u8() {
-            dst.push(b);
-        }
-
+        let _ = self.read_to_end(&mut dst);
         dst
     }
 
-    fn read_string(&mut self, length: usize) -> Result<String, Self::Error> {
+    fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>, Self::Error> {
         let mut dst = vec![0; length];
         self.read_exact(&mut dst)?;
+        Ok(dst)
+    }
+
+    fn read_string(&mut self, length: usize) -> Result<String, Self::Error> {
+        let dst = self.read_bytes(length)?;
 
         let mut text = String::with_capacity(length);
         for ch in dst.iter() {
@@ -112,40 +392,94 @@ where
         self.read_string(length)
     }
 
+    fn read_cstr(&mut self) -> Result<String, Self::Error> {
+        // Unlike `read_string`, this only consumes up to and including the null terminator, so
+        // anything following the string is left in the source.
+        let mut text = String::new();
+        loop {
+            match self.read_u8() {
+                Ok(0) => break,
+                Ok(ch) => text.push(char::from(ch)),
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(text)
+    }
+
+    fn read_length_prefixed_string_capped(&mut self, max: usize) -> Result<String, Self::Error> {
+        let length = self.read_u32::<byteorder::LittleEndian>()? as usize;
+        if length > max {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("string length {length} exceeds maximum of {max}"),
+            ));
+        }
+
+        self.read_string(length)
+    }
+
+    fn read_length_prefixed_string_be(&mut self) -> Result<String, Self::Error> {
+        let length = self.read_u32::<byteorder::BigEndian>()? as usize;
+        self.read_string(length)
+    }
+
     fn skip(&mut self, length: usize) -> Result<(), Self::Error> {
-        let mut dst = vec![0; length];
-        self.read_exact(&mut dst)?;
+        self.read_bytes(length)?;
         Ok(())
     }
 }
 
 impl<W> ShaiyaWriteExt for W
 where
-    W: std::io::Write + byteorder::WriteBytesExt,
+    W: std::io::Write + byteorder::WriteBytesExt + ?Sized,
 {
     type Error = std::io::Error;
 
+    fn write_fixed(&mut self, bytes: &[u8], length: usize) -> Result<(), Self::Error> {
+        if bytes.len() > length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("length {} exceeds field width of {length}", bytes.len()),
+            ));
+        }
+
+        self.write_all(bytes)?;
+        self.write_all(&vec![0; length - bytes.len()])?;
+        Ok(())
+    }
+
     fn write_string<T>(&mut self, text: T, length: usize) -> Result<(), Self::Error>
     where
         T: AsRef<str>,
     {
-        let mut dst = vec![0; length];
+        self.write_fixed(text.as_ref().as_bytes(), length)
+    }
+
+    fn write_length_prefixed_string<T>(&mut self, text: T) -> Result<(), Self::Error>
+    where
+        T: AsRef<str>,
+    {
         let bytes = text.as_ref().as_bytes();
-        dst[..bytes.len()].copy_from_slice(bytes);
+        let length_with_null_terminator = bytes.len() + 1;
 
-        self.write_all(&dst)?;
+        self.write_u32::<byteorder::LittleEndian>(length_with_null_terminator as u32)?;
+        self.write_all(bytes)?;
+        self.write_u8(0)?;
         Ok(())
     }
 
-    fn write_length_prefixed_string<T>(&mut self, text: T) -> Result<(), Self::Error>
+    fn write_length_prefixed_string_be<T>(&mut self, text: T) -> Result<(), Self::Error>
     where
         T: AsRef<str>,
     {
-        let text = text.as_ref();
-        let length_with_null_terminator = text.bytes().len() + 1;
+        let bytes = text.as_ref().as_bytes();
+        let length_with_null_terminator = bytes.len() + 1;
 
-        self.write_u32::<byteorder::LittleEndian>(length_with_null_terminator as u32)?;
-        self.write_string(text, length_with_null_terminator)?;
+        self.write_u32::<byteorder::BigEndian>(length_with_null_terminator as u32)?;
+        self.write_all(bytes)?;
+        self.write_u8(0)?;
         Ok(())
     }
 
@@ -153,3 +487,244 @@ where
         self.write_u8(if value { 1 } else { 0 })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{
+        is_version_mismatch, Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt,
+        TrailingBytes,
+    };
+    use byteorder::{ReadBytesExt, WriteBytesExt};
+    use std::io::Cursor;
+
+    #[test]
+    fn length_prefixed_string_empty() {
+        let mut dst = Vec::new();
+        dst.write_length_prefixed_string("").unwrap();
+        assert_eq!(dst, [1, 0, 0, 0, 0]);
+
+        let mut src = Cursor::new(dst.as_slice());
+        assert_eq!(src.read_length_prefixed_string().unwrap(), "");
+    }
+
+    #[test]
+    fn length_prefixed_string_single_char() {
+        let mut dst = Vec::new();
+        dst.write_length_prefixed_string("a").unwrap();
+        assert_eq!(dst, [2, 0, 0, 0, b'a', 0]);
+
+        let mut src = Cursor::new(dst.as_slice());
+        assert_eq!(src.read_length_prefixed_string().unwrap(), "a");
+    }
+
+    #[test]
+    fn length_prefixed_string_keeps_last_byte() {
+        let mut dst = Vec::new();
+        dst.write_length_prefixed_string("item.sdata").unwrap();
+        assert_eq!(dst.len(), 4 + 10 + 1);
+        assert_eq!(dst[4 + 9], b'a');
+        assert_eq!(*dst.last().unwrap(), 0);
+
+        let mut src = Cursor::new(dst.as_slice());
+        assert_eq!(src.read_length_prefixed_string().unwrap(), "item.sdata");
+    }
+
+    #[test]
+    fn length_prefixed_string_multibyte() {
+        let mut dst = Vec::new();
+        dst.write_length_prefixed_string("épée").unwrap();
+
+        let prefix = u32::from_le_bytes(dst[..4].try_into().unwrap()) as usize;
+        assert_eq!(prefix, "épée".len() + 1);
+        assert_eq!(dst.len(), 4 + prefix);
+        assert_eq!(&dst[4..4 + "épée".len()], "épée".as_bytes());
+    }
+
+    #[test]
+    fn length_prefixed_string_capped() {
+        let mut src = Cursor::new([0xFF, 0xFF, 0xFF, 0xFF]);
+        let err = src.read_length_prefixed_string_capped(256).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut dst = Vec::new();
+        dst.write_length_prefixed_string("data").unwrap();
+        let mut src = Cursor::new(dst.as_slice());
+        assert_eq!(src.read_length_prefixed_string_capped(256).unwrap(), "data");
+    }
+
+    #[test]
+    fn length_prefixed_string_big_endian() {
+        let text = "a".repeat(300);
+        let mut be = Vec::new();
+        be.write_length_prefixed_string_be(&text).unwrap();
+        assert_eq!(&be[..4], [0, 0, 1, 45]);
+
+        let mut src = Cursor::new(be.as_slice());
+        assert_eq!(src.read_length_prefixed_string_be().unwrap(), text);
+
+        let mut le = Vec::new();
+        le.write_length_prefixed_string(&text).unwrap();
+        assert_ne!(&le[..4], &be[..4]);
+        assert_eq!(&le[4..], &be[4..]);
+    }
+
+    #[test]
+    fn game_version_round_trip() {
+        for version in GameVersion::all() {
+            let parsed = version.to_string().parse::<GameVersion>().unwrap();
+            assert_eq!(parsed, *version);
+        }
+
+        assert_eq!("EP6v2".parse::<GameVersion>().unwrap(), GameVersion::Ep6v2);
+        assert!("ep7".parse::<GameVersion>().is_err());
+    }
+
+    #[test]
+    fn game_version_ordering() {
+        assert_eq!(
+            GameVersion::iter().collect::<Vec<_>>(),
+            [
+                GameVersion::Ep6v2,
+                GameVersion::Ep6,
+                GameVersion::Ep5,
+                GameVersion::Ep4
+            ]
+        );
+        assert!(GameVersion::iter().eq(GameVersion::all().iter().copied()));
+
+        assert!(GameVersion::Ep6.newer_than(GameVersion::Ep5));
+        assert!(!GameVersion::Ep6.newer_than(GameVersion::Ep6));
+        assert!(GameVersion::Ep4.older_than(GameVersion::Ep6v2));
+        assert!(!GameVersion::Ep6v2.older_than(GameVersion::Ep6));
+        assert!(GameVersion::Ep6.at_least(GameVersion::Ep6));
+        assert!(!GameVersion::Ep5.at_least(GameVersion::Ep6));
+    }
+
+    #[test]
+    fn consume_all_matches_byte_loop() {
+        let data = (0..1_000_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        let mut src = Cursor::new(data.as_slice());
+        src.skip(10).unwrap();
+        let mut expected = Vec::new();
+        while let Ok(b) = src.read_u8() {
+            expected.push(b);
+        }
+
+        let mut src = Cursor::new(data.as_slice());
+        src.skip(10).unwrap();
+        assert_eq!(src.consume_all(), expected);
+        assert!(src.consume_all().is_empty());
+    }
+
+    #[test]
+    fn cstr_stops_at_null() {
+        let mut src = Cursor::new(b"filter.txt\0\x2A\0\0\0trailing".as_slice());
+        assert_eq!(src.read_cstr().unwrap(), "filter.txt");
+        assert_eq!(src.read_u32::<byteorder::LittleEndian>().unwrap(), 42);
+        assert_eq!(src.read_cstr().unwrap(), "trailing");
+        assert_eq!(src.read_cstr().unwrap(), "");
+    }
+
+    #[test]
+    fn fixed_width_string_too_long() {
+        let mut dst = Vec::new();
+        dst.write_string("abcd", 4).unwrap();
+        assert_eq!(dst, b"abcd");
+
+        let err = dst.write_string("abcde", 4).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(dst.len(), 4);
+    }
+
+    #[test]
+    fn fixed_width_bytes() {
+        let mut dst = Vec::new();
+        dst.write_fixed(&[1, 2, 3], 3).unwrap();
+        dst.write_fixed(&[4], 3).unwrap();
+        assert_eq!(dst, [1, 2, 3, 4, 0, 0]);
+
+        let err = dst.write_fixed(&[1, 2, 3, 4], 3).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(dst.len(), 6);
+
+        let mut src = Cursor::new(dst.as_slice());
+        assert_eq!(src.read_bytes(4).unwrap(), [1, 2, 3, 4]);
+        assert_eq!(src.read_bytes(2).unwrap(), [0, 0]);
+        assert!(src.read_bytes(1).is_err());
+    }
+
+    /// A minimal fixed-width type, for testing the collection impls.
+    #[derive(Debug, PartialEq)]
+    struct Id(u16);
+
+    impl Serialize for Id {
+        type Error = std::io::Error;
+
+        fn versioned_serialize<T>(
+            &self,
+            dst: &mut T,
+            _version: GameVersion,
+        ) -> Result<(), Self::Error>
+        where
+            T: std::io::Write + WriteBytesExt,
+        {
+            dst.write_u16::<byteorder::LittleEndian>(self.0)
+        }
+    }
+
+    impl Deserialize for Id {
+        type Error = std::io::Error;
+
+        fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+        where
+            T: std::io::Read + ReadBytesExt,
+            Self: Sized,
+        {
+            Ok(Self(src.read_u16::<byteorder::LittleEndian>()?))
+        }
+    }
+
+    #[test]
+    fn vec_and_tuple_round_trip() {
+        let values = vec![(Id(1), Id(2)), (Id(3), Id(4))];
+
+        let mut dst = Vec::new();
+        values.serialize(&mut dst).unwrap();
+        assert_eq!(dst, [2, 0, 0, 0, 1, 0, 2, 0, 3, 0, 4, 0]);
+
+        let mut src = Cursor::new(dst.as_slice());
+        let decoded = Vec::<(Id, Id)>::deserialize(&mut src).unwrap();
+        assert_eq!(decoded, values);
+
+        let mut src = Cursor::new([0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(Vec::<(Id, Id)>::deserialize(&mut src).is_err());
+    }
+
+    #[test]
+    fn version_mismatch_errors() {
+        let eof = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+        assert!(is_version_mismatch(&eof));
+        assert!(is_version_mismatch(&TrailingBytes { count: 3 }.into()));
+
+        let corrupt = std::io::Error::new(std::io::ErrorKind::InvalidData, "checksum mismatch");
+        assert!(!is_version_mismatch(&corrupt));
+    }
+
+    #[test]
+    fn trait_object_sources() {
+        let mut dst: Box<dyn std::io::Write> = Box::new(Vec::new());
+        dst.write_length_prefixed_string("hello").unwrap();
+        dst.write_string("world", 8).unwrap();
+
+        let mut buf = Vec::new();
+        buf.write_length_prefixed_string("hello").unwrap();
+        buf.write_string("world", 8).unwrap();
+
+        let mut cursor = Cursor::new(buf.as_slice());
+        let src: &mut dyn std::io::Read = &mut cursor;
+        assert_eq!(src.read_length_prefixed_string().unwrap(), "hello");
+        assert_eq!(src.read_string(8).unwrap(), "world");
+        assert!(src.consume_all().is_empty());
+    }
+}
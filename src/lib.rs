@@ -1,5 +1,7 @@
 #[cfg(feature = "filestore")]
 pub mod fs;
 pub mod io;
+#[cfg(feature = "patch")]
+pub mod patch;
 #[cfg(feature = "protocol")]
 pub mod protocol;
@@ -0,0 +1,106 @@
+use crate::fs::{ImmutableFilestore, ReadableStorage};
+use bytes::Bytes;
+use std::sync::Arc;
+
+/// Serves files out of a shared [ImmutableFilestore], such as when hosting game files for a patch
+/// server. This is cheap to clone, and can be shared between any number of request handlers.
+#[derive(Clone)]
+pub struct PatchServer {
+    filestore: Arc<ImmutableFilestore>,
+    file_list: Arc<[String]>,
+}
+
+impl PatchServer {
+    /// Creates a patch server for a filestore. The path to every node is listed once here, rather
+    /// than on every call to [Self::file_list].
+    ///
+    /// # Arguments
+    /// * `filestore`   - The filestore to serve files from.
+    pub fn new(filestore: Arc<ImmutableFilestore>) -> Self {
+        let file_list = filestore.all_node_paths().into();
+        Self {
+            filestore,
+            file_list,
+        }
+    }
+
+    /// Fetches the contents of a file at a given path. This will return `None` if a node is not
+    /// found at the given path.
+    ///
+    /// # Arguments
+    /// * `path`    - The path to the file, relative to the root directory.
+    pub fn fetch(&self, path: &str) -> Option<Bytes> {
+        self.filestore.as_slice(path).map(Bytes::copy_from_slice)
+    }
+
+    /// Gets the path to every file contained within the filestore.
+    pub fn file_list(&self) -> &[String] {
+        &self.file_list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::{ImmutableFilestore, MutableFilestore, WritableStorage};
+    use crate::patch::PatchServer;
+    use std::sync::Arc;
+
+    #[test]
+    fn concurrent_fetch() {
+        let dir = std::env::temp_dir();
+        let prefix = format!("epeiros-{}-patch", std::process::id());
+        let header_path = dir.join(format!("{prefix}.sah"));
+        let data_path = dir.join(format!("{prefix}.saf"));
+
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", b"filter", false).unwrap();
+        fs.write("item/item.sdata", &[7; 1024], true).unwrap();
+        drop(fs);
+
+        let fs = ImmutableFilestore::open(&header_path, &data_path).unwrap();
+        let server = PatchServer::new(Arc::new(fs));
+        assert_eq!(server.file_list(), ["filter.txt", "item/item.sdata"]);
+        assert!(server.fetch("missing.txt").is_none());
+
+        let handles = (0..8)
+            .map(|_| {
+                let server = server.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        assert_eq!(server.fetch("filter.txt").unwrap(), &b"filter"[..]);
+                        assert_eq!(server.fetch("item/item.sdata").unwrap(), vec![7; 1024]);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn fetch_is_recorded() {
+        let dir = std::env::temp_dir();
+        let prefix = format!("epeiros-{}-patch-metrics", std::process::id());
+        let header_path = dir.join(format!("{prefix}.sah"));
+        let data_path = dir.join(format!("{prefix}.saf"));
+
+        let mut fs = MutableFilestore::create(&header_path, &data_path).unwrap();
+        fs.write("filter.txt", b"filter", false).unwrap();
+        fs.write("item/item.sdata", &[7; 1024], true).unwrap();
+        drop(fs);
+
+        let fs = Arc::new(ImmutableFilestore::open(&header_path, &data_path).unwrap());
+        let server = PatchServer::new(fs.clone());
+        server.fetch("filter.txt").unwrap();
+        server.fetch("item/item.sdata").unwrap();
+        server.fetch("item/item.sdata").unwrap();
+        assert!(server.fetch("missing.txt").is_none());
+
+        let metrics = fs.metrics();
+        assert_eq!(metrics.nodes_read, 3);
+        assert_eq!(metrics.bytes_read, 6 + 2 * 1024);
+    }
+}
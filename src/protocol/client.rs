@@ -5,6 +5,6 @@ pub use handshake::*;
 pub use login::*;
 
 packets!(LoginClientPacket {
-    LoginHandshakeResponse  => 0xA101,
-    LoginRequest            => 0xA102,
+    LoginHandshakeResponse(LOGIN_HANDSHAKE_RESPONSE)    => 0xA101,
+    LoginRequest(LOGIN_REQUEST)                         => 0xA102,
 });
@@ -1,11 +1,11 @@
-use crate::io::{Deserialize, GameVersion, Serialize};
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt};
 use aes::Aes128;
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use cipher::KeyIvInit;
 use ctr::Ctr128LE;
 use hmac::{Hmac, Mac};
 use rsa::traits::{PrivateKeyParts, PublicKeyParts};
-use rsa::{BigUint, RsaPrivateKey};
+use rsa::{BigUint, Pkcs1v15Encrypt, RsaPrivateKey};
 use sha2::Sha256;
 use std::io::{Read, Write};
 use thiserror::Error;
@@ -23,6 +23,9 @@ pub struct LoginHandshakeResponse {
 pub enum HandshakeResponseError {
     #[error("payload length did not match modulus (expected {expected:?}, got {actual:?})")]
     InvalidPayloadLength { expected: usize, actual: usize },
+
+    #[error("failed to decrypt padded payload: {0}")]
+    Padding(#[from] rsa::Error),
 }
 
 impl LoginHandshakeResponse {
@@ -49,26 +52,66 @@ impl LoginHandshakeResponse {
         Ok(decrypted.to_bytes_le())
     }
 
+    /// Decrypts a handshake response payload which was encrypted with PKCS#1 v1.5 padding, such
+    /// as with [LoginHandshakeRequest::encrypt_padded](crate::protocol::server::LoginHandshakeRequest::encrypt_padded).
+    /// The retail client never pads its payload, so this should only be used with clients which
+    /// expect it.
+    ///
+    /// # Arguments
+    /// * `private_key` - The private key.
+    pub fn decrypt_padded(
+        &self,
+        private_key: &RsaPrivateKey,
+    ) -> Result<Vec<u8>, HandshakeResponseError> {
+        Ok(private_key.decrypt(Pkcs1v15Encrypt, &self.payload)?)
+    }
+
     /// Decrypts the handshake response payload and performs a HmacSHA256 function to
-    /// generate an AES key pair.
+    /// generate the AES keys for the rest of the session.
     ///
     /// # Arguments
     /// * `private_key` - The private key.
     pub fn to_aes_keypair(
         &self,
         private_key: &RsaPrivateKey,
-    ) -> Result<(Aes128Ctr, Aes128Ctr), Box<dyn std::error::Error>> {
+    ) -> Result<HandshakeKeys, Box<dyn std::error::Error>> {
         let n = private_key.n().to_bytes_le();
         let payload = self.decrypt(private_key)?;
-        let mut mac = HmacSha256::new_from_slice(&payload)?;
-        mac.update(&n);
+        Ok(HandshakeKeys::derive(&payload, &n)?)
+    }
+}
+
+/// The ciphers which encrypt every packet after the handshake.
+///
+/// The protocol derives a single key and iv, which both directions share. Each direction still
+/// needs its own cipher, as a CTR cipher is stateful, and the packets sent and received advance
+/// their keystreams independently. Using one cipher for both directions would desynchronise the
+/// keystreams as soon as the first packet was exchanged.
+pub struct HandshakeKeys {
+    /// The cipher for packets sent to the peer.
+    pub send: Aes128Ctr,
+    /// The cipher for packets received from the peer.
+    pub recv: Aes128Ctr,
+}
+
+impl HandshakeKeys {
+    /// Derives the ciphers from the decrypted handshake payload. The HmacSHA256 of the modulus,
+    /// keyed with the payload, is split into the AES key and iv.
+    ///
+    /// # Arguments
+    /// * `payload` - The decrypted handshake payload.
+    /// * `modulus` - The little-endian modulus of the server's public key.
+    fn derive(payload: &[u8], modulus: &[u8]) -> Result<Self, hmac::digest::InvalidLength> {
+        let mut mac = HmacSha256::new_from_slice(payload)?;
+        mac.update(modulus);
 
         let key_base = mac.finalize().into_bytes();
         let (key, iv) = key_base.split_at(16);
 
-        let send_cipher = Aes128Ctr::new(key.into(), iv.into());
-        let recv_cipher = Aes128Ctr::new(key.into(), iv.into());
-        Ok((send_cipher, recv_cipher))
+        Ok(Self {
+            send: Aes128Ctr::new(key.into(), iv.into()),
+            recv: Aes128Ctr::new(key.into(), iv.into()),
+        })
     }
 }
 
@@ -102,9 +145,66 @@ impl Deserialize for LoginHandshakeResponse {
         Self: Sized,
     {
         let payload_length = src.read_u8()? as usize;
-        let mut payload = vec![0; payload_length];
-        src.read_exact(&mut payload)?;
+        let payload = src.read_bytes(payload_length)?;
 
         Ok(Self { payload })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{Deserialize, Serialize};
+    use crate::protocol::client::{HandshakeKeys, LoginHandshakeResponse};
+    use crate::protocol::server::LoginHandshakeRequest;
+    use cipher::StreamCipher;
+    use rsa::rand_core::OsRng;
+    use rsa::RsaPrivateKey;
+    use std::io::Cursor;
+
+    #[test]
+    fn padded_round_trip() {
+        let private_key = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let mut dst = Vec::new();
+        LoginHandshakeRequest::new(&private_key.to_public_key())
+            .serialize(&mut dst)
+            .unwrap();
+        let request = LoginHandshakeRequest::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+
+        let input = [0x5A; 32];
+        let response = LoginHandshakeResponse {
+            payload: request.encrypt_padded(&mut OsRng, &input).unwrap(),
+        };
+        assert_ne!(response.payload, request.encrypt(&input));
+        assert_eq!(response.decrypt_padded(&private_key).unwrap(), input);
+
+        let raw = LoginHandshakeResponse {
+            payload: request.encrypt(&input),
+        };
+        assert!(raw.decrypt_padded(&private_key).is_err());
+    }
+
+    #[test]
+    fn key_derivation_vector() {
+        let payload = (1..=32).collect::<Vec<u8>>();
+        let mut keys = HandshakeKeys::derive(&payload, &[0xC3; 16]).unwrap();
+
+        // The key and iv are 25c165e0e226dd01dc988baf714c3965 and 0b787a2e7f45ee1ea45ab5c46b96e6fd,
+        // so the first block of the keystream is the iv encrypted with the key.
+        let expected = [
+            0x32, 0xB5, 0xA6, 0x79, 0x0D, 0x10, 0xEE, 0x67, 0x4A, 0x38, 0x46, 0xBC, 0x49, 0x46,
+            0x03, 0x05,
+        ];
+        let mut sent = [0; 16];
+        keys.send.apply_keystream(&mut sent);
+        assert_eq!(sent, expected);
+
+        let mut received = [0; 16];
+        keys.recv.apply_keystream(&mut received);
+        assert_eq!(received, expected);
+
+        // Each direction advances its keystream independently.
+        let mut sent = [0; 16];
+        keys.send.apply_keystream(&mut sent);
+        assert_ne!(sent, expected);
+    }
+}
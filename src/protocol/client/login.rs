@@ -34,3 +34,38 @@ impl Deserialize for LoginRequest {
         Ok(Self { username, password })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{Deserialize, Serialize};
+    use crate::protocol::client::LoginRequest;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trip() {
+        let request = LoginRequest {
+            username: "a".repeat(32),
+            password: "hunter2".to_owned(),
+        };
+
+        let mut dst = Vec::new();
+        request.serialize(&mut dst).unwrap();
+        assert_eq!(dst.len(), 51);
+
+        let decoded = LoginRequest::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(decoded.username, request.username);
+        assert_eq!(decoded.password, request.password);
+    }
+
+    #[test]
+    fn over_length_username() {
+        let request = LoginRequest {
+            username: "a".repeat(40),
+            password: "hunter2".to_owned(),
+        };
+
+        let mut dst = Vec::new();
+        let err = request.serialize(&mut dst).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::io::{ErrorKind, Read, Write};
+
+/// The length of the frame's length prefix.
+const LENGTH_PREFIX_SIZE: usize = 2;
+
+/// Reads a single frame from a source. A frame is prefixed with a little-endian `u16` containing
+/// the length of the entire frame, including the prefix itself. The returned body does not
+/// include the prefix, and begins with the packet opcode.
+///
+/// # Arguments
+/// * `src` - The source.
+pub fn read_frame<R>(src: &mut R) -> Result<Vec<u8>, std::io::Error>
+where
+    R: Read,
+{
+    let length = src.read_u16::<LE>()? as usize;
+    if length < LENGTH_PREFIX_SIZE {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("frame length {length} is shorter than its prefix"),
+        ));
+    }
+
+    let mut body = vec![0; length - LENGTH_PREFIX_SIZE];
+    src.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// Writes a single frame to a destination. The body is prefixed with a little-endian `u16`
+/// containing the length of the entire frame, including the prefix itself.
+///
+/// # Arguments
+/// * `dst`     - The destination.
+/// * `body`    - The frame body, beginning with the packet opcode.
+pub fn write_frame<W>(dst: &mut W, body: &[u8]) -> Result<(), std::io::Error>
+where
+    W: Write,
+{
+    let length = body.len() + LENGTH_PREFIX_SIZE;
+    if length > u16::MAX as usize {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("frame length {length} exceeds the maximum of {}", u16::MAX),
+        ));
+    }
+
+    dst.write_u16::<LE>(length as u16)?;
+    dst.write_all(body)?;
+    Ok(())
+}
+
+/// A variable-length field which takes up the rest of a packet's frame, with no length prefix of its
+/// own. Its length is known only from the frame boundary, so this must be the last field of a
+/// packet, and the packet must be decoded from exactly one frame body, such as with the
+/// `from_frame` function that every packet enum provides.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RestOfFrame(pub Vec<u8>);
+
+impl Serialize for RestOfFrame {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        dst.write_all(&self.0)
+    }
+}
+
+impl Deserialize for RestOfFrame {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        Ok(Self(src.consume_all()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{read_frame, write_frame};
+    use std::io::Cursor;
+
+    #[test]
+    fn frame_round_trip() {
+        let body = [0x01, 0xA2, 0xDE, 0xAD, 0xBE, 0xEF];
+        let mut dst = Vec::new();
+        write_frame(&mut dst, &body).unwrap();
+        write_frame(&mut dst, &[]).unwrap();
+        assert_eq!(&dst[..2], [8, 0]);
+
+        let mut src = Cursor::new(dst.as_slice());
+        assert_eq!(read_frame(&mut src).unwrap(), body);
+        assert!(read_frame(&mut src).unwrap().is_empty());
+        assert!(read_frame(&mut src).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+pub mod client;
+pub mod server;
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{Deserialize, Serialize};
+    use crate::protocol::game::client::{ChatRequest, GameClientPacket, MoveRequest};
+    use crate::protocol::game::server::{CharacterMoved, ChatMessage, GameServerPacket};
+    use std::io::Cursor;
+
+    /// Serializes a packet, and deserializes it again through its packet enum.
+    macro_rules! round_trip {
+        ($packet_enum:ident, $packet:expr) => {{
+            let packet = $packet_enum::from($packet);
+            let mut dst = Vec::new();
+            packet.serialize(&mut dst).unwrap();
+            assert_eq!(&dst[..2], &packet.opcode().to_le_bytes());
+
+            let mut src = Cursor::new(dst.as_slice());
+            let decoded = $packet_enum::deserialize(&mut src).unwrap();
+            assert_eq!(src.position() as usize, dst.len());
+            decoded
+        }};
+    }
+
+    #[test]
+    fn client_packets_round_trip() {
+        let request = MoveRequest {
+            motion: 1,
+            direction: 90,
+            x: 1024.5,
+            y: 12.25,
+            z: 768.0,
+        };
+        match round_trip!(GameClientPacket, request.clone()) {
+            GameClientPacket::MoveRequest(decoded) => assert_eq!(decoded, request),
+            packet => panic!("unexpected packet {}", packet.name()),
+        }
+
+        let request = ChatRequest {
+            message: "hello world".to_owned(),
+        };
+        match round_trip!(GameClientPacket, request.clone()) {
+            GameClientPacket::ChatRequest(decoded) => assert_eq!(decoded, request),
+            packet => panic!("unexpected packet {}", packet.name()),
+        }
+    }
+
+    #[test]
+    fn server_packets_round_trip() {
+        let moved = CharacterMoved {
+            character_id: 42,
+            motion: 0,
+            direction: 180,
+            x: -5.5,
+            y: 0.0,
+            z: 300.75,
+        };
+        match round_trip!(GameServerPacket, moved.clone()) {
+            GameServerPacket::CharacterMoved(decoded) => assert_eq!(decoded, moved),
+            packet => panic!("unexpected packet {}", packet.name()),
+        }
+
+        let message = ChatMessage {
+            character_id: 42,
+            message: "hello world".to_owned(),
+        };
+        match round_trip!(GameServerPacket, message.clone()) {
+            GameServerPacket::ChatMessage(decoded) => assert_eq!(decoded, message),
+            packet => panic!("unexpected packet {}", packet.name()),
+        }
+    }
+
+    #[test]
+    fn chat_message_too_long() {
+        let request = ChatRequest {
+            message: "a".repeat(256),
+        };
+        let mut dst = Vec::new();
+        assert!(request.serialize(&mut dst).is_err());
+    }
+}
@@ -0,0 +1,10 @@
+mod chat;
+mod movement;
+
+pub use chat::*;
+pub use movement::*;
+
+packets!(GameClientPacket {
+    MoveRequest(MOVE_REQUEST)   => 0x0501,
+    ChatRequest(CHAT_REQUEST)   => 0x1101,
+});
@@ -0,0 +1,49 @@
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+
+/// Sent by the client when the player says something in the normal chat channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatRequest {
+    pub message: String,
+}
+
+impl Serialize for ChatRequest {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        let length = chat_message_length(&self.message)?;
+        dst.write_u8(length)?;
+        dst.write_string(&self.message, length as usize)
+    }
+}
+
+impl Deserialize for ChatRequest {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        let length = src.read_u8()? as usize;
+        let message = src.read_string(length)?;
+        Ok(Self { message })
+    }
+}
+
+/// Gets the length of a chat message, which is prefixed with a single byte.
+///
+/// # Arguments
+/// * `message` - The chat message.
+pub(crate) fn chat_message_length(message: &str) -> Result<u8, std::io::Error> {
+    u8::try_from(message.len()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("chat message length {} exceeds 255", message.len()),
+        )
+    })
+}
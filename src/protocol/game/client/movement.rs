@@ -0,0 +1,47 @@
+use crate::io::{Deserialize, GameVersion, Serialize};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::io::{Read, Write};
+
+/// Sent by the client when the player's character moves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveRequest {
+    pub motion: u8,
+    pub direction: u16,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Serialize for MoveRequest {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        dst.write_u8(self.motion)?;
+        dst.write_u16::<LE>(self.direction)?;
+        dst.write_f32::<LE>(self.x)?;
+        dst.write_f32::<LE>(self.y)?;
+        dst.write_f32::<LE>(self.z)?;
+        Ok(())
+    }
+}
+
+impl Deserialize for MoveRequest {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        Ok(Self {
+            motion: src.read_u8()?,
+            direction: src.read_u16::<LE>()?,
+            x: src.read_f32::<LE>()?,
+            y: src.read_f32::<LE>()?,
+            z: src.read_f32::<LE>()?,
+        })
+    }
+}
@@ -0,0 +1,10 @@
+mod chat;
+mod movement;
+
+pub use chat::*;
+pub use movement::*;
+
+packets!(GameServerPacket {
+    CharacterMoved(CHARACTER_MOVED) => 0x0501,
+    ChatMessage(CHAT_MESSAGE)       => 0x1101,
+});
@@ -0,0 +1,43 @@
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt};
+use crate::protocol::game::client::chat_message_length;
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::io::{Read, Write};
+
+/// Sent to nearby players when a character says something in the normal chat channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub character_id: u32,
+    pub message: String,
+}
+
+impl Serialize for ChatMessage {
+    type Error = std::io::Error;
+
+    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    where
+        T: Write + WriteBytesExt,
+    {
+        let length = chat_message_length(&self.message)?;
+        dst.write_u32::<LE>(self.character_id)?;
+        dst.write_u8(length)?;
+        dst.write_string(&self.message, length as usize)
+    }
+}
+
+impl Deserialize for ChatMessage {
+    type Error = std::io::Error;
+
+    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    where
+        T: Read + ReadBytesExt,
+        Self: Sized,
+    {
+        let character_id = src.read_u32::<LE>()?;
+        let length = src.read_u8()? as usize;
+        let message = src.read_string(length)?;
+        Ok(Self {
+            character_id,
+            message,
+        })
+    }
+}
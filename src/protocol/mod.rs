@@ -1,9 +1,25 @@
+/// Defines an enum of packets, each with its opcode. Packets are expected to consume exactly the
+/// bytes of their frame. A packet whose last field has no length of its own can use
+/// [RestOfFrame] to read up to the frame boundary, as long as it is decoded with the generated
+/// `from_frame` function rather than from a stream of frames.
 macro_rules! packets {
     (
         $ident:ident {
-            $($packet:ident => $opcode:literal),* $(,)?
+            $($packet:ident($constant:ident) => $opcode:literal),* $(,)?
         }
     ) => {
+        /// The opcode of every packet, which can be matched against before the packet body has
+        /// been deserialized.
+        pub mod opcodes {
+            $(
+                #[doc = concat!("The opcode of [", stringify!($packet), "](super::", stringify!($packet), ").")]
+                pub const $constant: u16 = $opcode;
+            )*
+
+            /// Every opcode, paired with the name of its packet.
+            pub const ALL: &[(u16, &str)] = &[$(($constant, stringify!($packet))),*];
+        }
+
         #[derive(Debug, Clone)]
         pub enum $ident {
             $(
@@ -15,8 +31,48 @@ macro_rules! packets {
             pub fn opcode(&self) -> u16 {
                 match self {
                     $(
-                        $ident::$packet(_)  => $opcode,
+                        $ident::$packet(_)  => opcodes::$constant,
+                    )*
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        $ident::$packet(_)  => stringify!($packet),
+                    )*
+                }
+            }
+
+            /// Decodes a packet from the body of a single frame, as read by
+            /// [read_frame](crate::protocol::read_frame). The frame body is the boundary of the
+            /// packet, which lets a trailing [RestOfFrame](crate::protocol::RestOfFrame) field
+            /// read up to the end of the frame. Any bytes which the packet leaves unread are
+            /// treated as an error.
+            ///
+            /// # Arguments
+            /// * `body`    - The frame body, beginning with the packet opcode.
+            /// * `version` - The game version.
+            pub fn from_frame(body: &[u8], version: $crate::io::GameVersion) -> Result<Self, std::io::Error> {
+                let mut src = std::io::Cursor::new(body);
+                let packet = <Self as $crate::io::Deserialize>::versioned_deserialize(&mut src, version)?;
+
+                let unread = body.len() - src.position() as usize;
+                if unread != 0 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{} left {unread} unread bytes in its frame", packet.name()),
+                    ));
+                }
+                Ok(packet)
+            }
+
+            pub fn opcode_name(opcode: u16) -> Option<&'static str> {
+                match opcode {
+                    $(
+                        opcodes::$constant => Some(stringify!($packet)),
                     )*
+                    _ => None,
                 }
             }
         }
@@ -31,7 +87,7 @@ macro_rules! packets {
                 let opcode = src.read_u16::<byteorder::LittleEndian>()?;
                 match opcode {
                     $(
-                        opcode if opcode == $opcode => Ok($ident::$packet($packet::versioned_deserialize(src, version)?)),
+                        opcodes::$constant => Ok($ident::$packet($packet::versioned_deserialize(src, version)?)),
                     )*
                     _ => Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("opcode does not exist: {:#06X}", opcode))),
                 }
@@ -65,5 +121,145 @@ macro_rules! packets {
     };
 }
 
+mod frame;
+
 pub mod client;
+pub mod game;
 pub mod server;
+
+pub use frame::*;
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{GameVersion, Serialize};
+    use crate::protocol::client::{LoginClientPacket, LoginRequest};
+    use crate::protocol::server::{opcodes, LoginServerPacket, ServerList};
+    use crate::protocol::{read_frame, write_frame, RestOfFrame};
+    use blob::{Blob, BlobPacket};
+    use std::io::Cursor;
+
+    #[test]
+    fn opcode_names() {
+        assert_eq!(LoginServerPacket::opcode_name(0xA201), Some("ServerList"));
+        assert_eq!(LoginServerPacket::opcode_name(0xFFFF), None);
+
+        let packet = LoginClientPacket::from(LoginRequest {
+            username: String::new(),
+            password: String::new(),
+        });
+        assert_eq!(packet.name(), "LoginRequest");
+    }
+
+    /// A packet enum for testing, with a packet which has a trailing payload.
+    mod blob {
+        use crate::io::{Deserialize, GameVersion, Serialize};
+        use crate::protocol::RestOfFrame;
+        use byteorder::{ReadBytesExt, WriteBytesExt};
+
+        /// A packet with a trailing payload which takes up the rest of its frame.
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct Blob {
+            pub kind: u8,
+            pub payload: RestOfFrame,
+        }
+
+        impl Serialize for Blob {
+            type Error = std::io::Error;
+
+            fn versioned_serialize<T>(
+                &self,
+                dst: &mut T,
+                version: GameVersion,
+            ) -> Result<(), Self::Error>
+            where
+                T: std::io::Write + WriteBytesExt,
+            {
+                dst.write_u8(self.kind)?;
+                self.payload.versioned_serialize(dst, version)
+            }
+        }
+
+        impl Deserialize for Blob {
+            type Error = std::io::Error;
+
+            fn versioned_deserialize<T>(
+                src: &mut T,
+                version: GameVersion,
+            ) -> Result<Self, Self::Error>
+            where
+                T: std::io::Read + ReadBytesExt,
+                Self: Sized,
+            {
+                let kind = src.read_u8()?;
+                let payload = RestOfFrame::versioned_deserialize(src, version)?;
+                Ok(Self { kind, payload })
+            }
+        }
+
+        packets!(BlobPacket {
+            Blob(BLOB) => 0x0001,
+        });
+    }
+
+    #[test]
+    fn rest_of_frame_payload() {
+        let blob = Blob {
+            kind: 3,
+            payload: RestOfFrame(vec![0xDE, 0xAD, 0xBE, 0xEF]),
+        };
+        assert_eq!(blob::opcodes::ALL, [(0x0001, "Blob")]);
+        assert_eq!(BlobPacket::opcode_name(0x0001), Some("Blob"));
+
+        let mut body = Vec::new();
+        BlobPacket::from(blob.clone()).serialize(&mut body).unwrap();
+
+        let mut dst = Vec::new();
+        write_frame(&mut dst, &body).unwrap();
+        write_frame(&mut dst, &[0x01, 0x00, 7]).unwrap();
+
+        let mut src = Cursor::new(dst.as_slice());
+        let body = read_frame(&mut src).unwrap();
+        let BlobPacket::Blob(decoded) = BlobPacket::from_frame(&body, GameVersion::Ep5).unwrap();
+        assert_eq!(decoded, blob);
+
+        let body = read_frame(&mut src).unwrap();
+        let BlobPacket::Blob(decoded) = BlobPacket::from_frame(&body, GameVersion::Ep5).unwrap();
+        assert_eq!(decoded.kind, 7);
+        assert!(decoded.payload.0.is_empty());
+    }
+
+    #[test]
+    fn unread_bytes_are_rejected() {
+        let mut body = vec![0x02, 0xA1];
+        body.resize(2 + 51, 0);
+        assert!(LoginClientPacket::from_frame(&body, GameVersion::Ep5).is_ok());
+
+        body.push(0xFF);
+        let err = LoginClientPacket::from_frame(&body, GameVersion::Ep5).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn opcode_constants() {
+        assert_eq!(opcodes::LOGIN_HANDSHAKE_REQUEST, 0xA101);
+        assert_eq!(opcodes::SERVER_LIST, 0xA201);
+
+        let packet = LoginServerPacket::from(ServerList {
+            servers: Vec::new(),
+        });
+        assert_eq!(packet.opcode(), opcodes::SERVER_LIST);
+        assert_eq!(opcodes::ALL.len(), 3);
+        for (opcode, name) in opcodes::ALL {
+            assert_eq!(LoginServerPacket::opcode_name(*opcode), Some(*name));
+        }
+
+        assert_eq!(
+            crate::protocol::client::opcodes::LOGIN_REQUEST,
+            LoginClientPacket::from(LoginRequest {
+                username: String::new(),
+                password: String::new(),
+            })
+            .opcode()
+        );
+    }
+}
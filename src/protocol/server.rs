@@ -7,7 +7,7 @@ pub use login::*;
 pub use serverlist::*;
 
 packets!(LoginServerPacket {
-    LoginHandshakeRequest   => 0xA101,
-    LoginResponse           => 0xA102,
-    ServerList              => 0xA201,
+    LoginHandshakeRequest(LOGIN_HANDSHAKE_REQUEST)  => 0xA101,
+    LoginResponse(LOGIN_RESPONSE)                   => 0xA102,
+    ServerList(SERVER_LIST)                         => 0xA201,
 });
@@ -1,7 +1,8 @@
-use crate::io::{Deserialize, GameVersion, Serialize};
+use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt};
 use byteorder::{ReadBytesExt, WriteBytesExt};
+use rsa::rand_core::CryptoRngCore;
 use rsa::traits::PublicKeyParts;
-use rsa::{BigUint, RsaPublicKey};
+use rsa::{BigUint, Pkcs1v15Encrypt, RsaPublicKey};
 use std::io::{Read, Write};
 
 /// The expected capacity of the exponent. Regardless of the actual size of the exponent, the client
@@ -40,6 +41,24 @@ impl LoginHandshakeRequest {
 
         input.modpow(&e, &n).to_bytes_le()
     }
+
+    /// Encrypts an input payload with the public key contained within this handshake request,
+    /// using PKCS#1 v1.5 padding. The retail client doesn't understand padded payloads, so this
+    /// should only be used with clients which expect it. Unlike [Self::encrypt], the output is
+    /// big-endian, as specified by PKCS#1.
+    ///
+    /// # Arguments
+    /// * `rng`     - The source of randomness for the padding.
+    /// * `input`   - The payload to encrypt.
+    pub fn encrypt_padded<R>(&self, rng: &mut R, input: &[u8]) -> Result<Vec<u8>, rsa::Error>
+    where
+        R: CryptoRngCore,
+    {
+        let e = BigUint::from_bytes_le(&self.exponent);
+        let n = BigUint::from_bytes_le(&self.modulus);
+
+        RsaPublicKey::new(n, e)?.encrypt(rng, Pkcs1v15Encrypt, input)
+    }
 }
 
 impl Deserialize for LoginHandshakeRequest {
@@ -55,10 +74,8 @@ impl Deserialize for LoginHandshakeRequest {
         let modulus_length = src.read_u8()? as usize;
 
         let mut exponent = vec![0; EXPONENT_CAPACITY];
-        let mut modulus = vec![0; modulus_length];
-
         let actual_exponent_length = src.read(&mut exponent)?;
-        src.read_exact(&mut modulus)?;
+        let modulus = src.read_bytes(modulus_length)?;
         if actual_exponent_length != expected_exponent_length {
             tracing::error!(
                 actual_exponent_length,
@@ -85,9 +102,7 @@ impl Serialize for LoginHandshakeRequest {
         // Shaiya is whack and expects to be able to read the exponent padded to 64 bytes, and
         // the modulus padded to 128 bytes. It will crash if this data is not available and valid.
         // Thankfully due to using a 1024-bit key, we don't need to care about padding the modulus.
-        let mut padded_exponent = [0; EXPONENT_CAPACITY];
-        padded_exponent[..self.exponent.len()].copy_from_slice(&self.exponent);
-        dst.write_all(&padded_exponent)?;
+        dst.write_fixed(&self.exponent, EXPONENT_CAPACITY)?;
         dst.write_all(&self.modulus)?;
         Ok(())
     }
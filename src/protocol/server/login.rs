@@ -1,17 +1,42 @@
 use crate::io::{Deserialize, GameVersion, Serialize};
 use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::fmt;
 use std::io::{Read, Write};
+use std::str::FromStr;
+use thiserror::Error;
 
 #[derive(Debug, Clone)]
 pub enum LoginResponse {
     Success {
         user_id: u32,
-        privilege: u8,
-        identity: u128,
+        privilege: Privilege,
+        identity: Identity,
     },
     Fail(LoginErrorCode),
 }
 
+/// The session identity issued to a client when it logs in, which is presented again when
+/// connecting to a game server. It's displayed and parsed as 32 hexadecimal digits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Identity(pub u128);
+
+/// The error returned when parsing an [Identity] from a string which isn't exactly 32 hexadecimal
+/// digits.
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("an identity must be exactly 32 hexadecimal digits")]
+pub struct ParseIdentityError;
+
+/// The privilege level of an account, which is sent to the client as a single byte.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Privilege {
+    Player,
+    Admin,
+    GameMaster,
+    GameMasterAssistant,
+    GameSage,
+    Unknown(u8),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LoginErrorCode {
     AccountDoesntExist = 1,
@@ -35,8 +60,8 @@ impl Serialize for LoginResponse {
             } => {
                 dst.write_u8(0)?; // success code
                 dst.write_u32::<byteorder::LittleEndian>(*user_id)?;
-                dst.write_u8(*privilege)?;
-                dst.write_u128::<byteorder::LittleEndian>(*identity)?;
+                dst.write_u8((*privilege).into())?;
+                dst.write_u128::<byteorder::LittleEndian>(identity.0)?;
             }
             LoginResponse::Fail(error) => {
                 dst.write_u8(error.clone() as u8)?;
@@ -59,8 +84,8 @@ impl Deserialize for LoginResponse {
         match status {
             0 => {
                 let user_id = src.read_u32::<byteorder::LittleEndian>()?;
-                let privilege = src.read_u8()?;
-                let identity = src.read_u128::<byteorder::LittleEndian>()?;
+                let privilege = Privilege::from(src.read_u8()?);
+                let identity = Identity(src.read_u128::<byteorder::LittleEndian>()?);
                 Ok(Self::Success {
                     user_id,
                     privilege,
@@ -74,3 +99,119 @@ impl Deserialize for LoginResponse {
         }
     }
 }
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl FromStr for Identity {
+    type Err = ParseIdentityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `from_str_radix` also accepts a sign and fewer digits, which `Display` never produces.
+        if s.len() != 32 || !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(ParseIdentityError);
+        }
+        u128::from_str_radix(s, 16)
+            .map(Self)
+            .map_err(|_| ParseIdentityError)
+    }
+}
+
+impl From<u8> for Privilege {
+    fn from(id: u8) -> Self {
+        match id {
+            0 => Self::Player,
+            16 => Self::Admin,
+            32 => Self::GameMaster,
+            48 => Self::GameMasterAssistant,
+            64 => Self::GameSage,
+            _ => Self::Unknown(id),
+        }
+    }
+}
+
+impl From<Privilege> for u8 {
+    fn from(privilege: Privilege) -> Self {
+        match privilege {
+            Privilege::Player => 0,
+            Privilege::Admin => 16,
+            Privilege::GameMaster => 32,
+            Privilege::GameMasterAssistant => 48,
+            Privilege::GameSage => 64,
+            Privilege::Unknown(id) => id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{Deserialize, Serialize};
+    use crate::protocol::server::{Identity, LoginResponse, ParseIdentityError, Privilege};
+    use std::io::Cursor;
+
+    #[test]
+    fn privilege_round_trip() {
+        let privileges = [
+            Privilege::Player,
+            Privilege::Admin,
+            Privilege::GameMaster,
+            Privilege::GameMasterAssistant,
+            Privilege::GameSage,
+            Privilege::Unknown(0xFF),
+        ];
+
+        for privilege in privileges {
+            assert_eq!(Privilege::from(u8::from(privilege)), privilege);
+
+            let response = LoginResponse::Success {
+                user_id: 1,
+                privilege,
+                identity: Identity(0),
+            };
+            let mut dst = Vec::new();
+            response.serialize(&mut dst).unwrap();
+            assert_eq!(dst.len(), 22);
+
+            let mut src = Cursor::new(dst.as_slice());
+            match LoginResponse::deserialize(&mut src).unwrap() {
+                LoginResponse::Success {
+                    privilege: decoded, ..
+                } => assert_eq!(decoded, privilege),
+                LoginResponse::Fail(error) => panic!("unexpected failure {error:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn identity_round_trip() {
+        let identity = Identity(0x0123456789ABCDEF00112233445566);
+        let text = identity.to_string();
+        assert_eq!(text, "000123456789abcdef00112233445566");
+        assert_eq!(text.parse::<Identity>().unwrap(), identity);
+        assert!("not an identity".parse::<Identity>().is_err());
+        assert_eq!(text.to_uppercase().parse::<Identity>().unwrap(), identity);
+        for invalid in ["1", "+00123456789abcdef00112233445566", &format!("{text}0")] {
+            assert_eq!(invalid.parse::<Identity>(), Err(ParseIdentityError));
+        }
+
+        let response = LoginResponse::Success {
+            user_id: 1,
+            privilege: Privilege::Player,
+            identity,
+        };
+        let mut dst = Vec::new();
+        response.serialize(&mut dst).unwrap();
+        assert_eq!(&dst[6..], &identity.0.to_le_bytes());
+
+        let mut src = Cursor::new(dst.as_slice());
+        match LoginResponse::deserialize(&mut src).unwrap() {
+            LoginResponse::Success {
+                identity: decoded, ..
+            } => assert_eq!(decoded, identity),
+            LoginResponse::Fail(error) => panic!("unexpected failure {error:?}"),
+        }
+    }
+}
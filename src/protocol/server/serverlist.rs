@@ -1,4 +1,7 @@
-use crate::io::{Deserialize, GameVersion, Serialize, ShaiyaReadExt, ShaiyaWriteExt};
+use crate::io::{
+    deserialize_items, serialize_items, Deserialize, GameVersion, Serialize, ShaiyaReadExt,
+    ShaiyaWriteExt,
+};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{Read, Write};
 
@@ -7,11 +10,13 @@ pub struct ServerList {
     pub servers: Vec<ServerEntry>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ServerStatus {
     Normal,
     Locked,
     Closed,
+    Maintenance,
+    New,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +35,8 @@ impl ServerStatus {
             ServerStatus::Normal => 0,
             ServerStatus::Locked => 1,
             ServerStatus::Closed => 2,
+            ServerStatus::Maintenance => 3,
+            ServerStatus::New => 4,
         }
     }
 
@@ -43,6 +50,8 @@ impl ServerStatus {
             0 => Some(ServerStatus::Normal),
             1 => Some(ServerStatus::Locked),
             2 => Some(ServerStatus::Closed),
+            3 => Some(ServerStatus::Maintenance),
+            4 => Some(ServerStatus::New),
             _ => None,
         }
     }
@@ -51,30 +60,26 @@ impl ServerStatus {
 impl Serialize for ServerList {
     type Error = std::io::Error;
 
-    fn versioned_serialize<T>(&self, dst: &mut T, _version: GameVersion) -> Result<(), Self::Error>
+    fn versioned_serialize<T>(&self, dst: &mut T, version: GameVersion) -> Result<(), Self::Error>
     where
         T: Write + WriteBytesExt,
     {
+        // The server list uses a single byte for its count, rather than the usual `u32`.
         dst.write_u8(self.servers.len() as u8)?;
-        self.servers.iter().try_for_each(|e| e.serialize(dst))?;
-        Ok(())
+        serialize_items(dst, &self.servers, version)
     }
 }
 
 impl Deserialize for ServerList {
     type Error = std::io::Error;
 
-    fn versioned_deserialize<T>(src: &mut T, _version: GameVersion) -> Result<Self, Self::Error>
+    fn versioned_deserialize<T>(src: &mut T, version: GameVersion) -> Result<Self, Self::Error>
     where
         T: Read + ReadBytesExt,
         Self: Sized,
     {
-        let qty = src.read_u8()?;
-        let mut servers = Vec::with_capacity(qty as usize);
-        for _ in 0..qty {
-            servers.push(ServerEntry::deserialize(src)?);
-        }
-
+        let qty = src.read_u8()? as usize;
+        let servers = deserialize_items(src, qty, version)?;
         Ok(Self { servers })
     }
 }
@@ -127,3 +132,85 @@ impl Deserialize for ServerEntry {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{Deserialize, Serialize};
+    use crate::protocol::server::{ServerEntry, ServerList, ServerStatus};
+    use std::io::Cursor;
+
+    #[test]
+    fn server_status_round_trip() {
+        let statuses = [
+            ServerStatus::Normal,
+            ServerStatus::Locked,
+            ServerStatus::Closed,
+            ServerStatus::Maintenance,
+            ServerStatus::New,
+        ];
+
+        for status in statuses {
+            let entry = ServerEntry {
+                id: 1,
+                status: status.clone(),
+                player_count: 10,
+                player_capacity: 100,
+                name: "Teos".to_owned(),
+            };
+
+            let mut dst = Vec::new();
+            entry.serialize(&mut dst).unwrap();
+
+            let mut src = Cursor::new(dst.as_slice());
+            let decoded = ServerEntry::deserialize(&mut src).unwrap();
+            assert_eq!(decoded.status, status);
+        }
+    }
+
+    #[test]
+    fn unknown_server_status() {
+        let mut buf = vec![1, 0xFF, 0, 0, 0, 0];
+        buf.extend_from_slice(&[0; 32]);
+
+        let mut src = Cursor::new(buf.as_slice());
+        assert!(ServerEntry::deserialize(&mut src).is_err());
+    }
+
+    #[test]
+    fn server_entries_round_trip() {
+        let servers = vec![
+            ServerEntry {
+                id: 1,
+                status: ServerStatus::Normal,
+                player_count: 10,
+                player_capacity: 100,
+                name: "Teos".to_owned(),
+            },
+            ServerEntry {
+                id: 2,
+                status: ServerStatus::New,
+                player_count: 0,
+                player_capacity: 50,
+                name: "Grag".to_owned(),
+            },
+        ];
+
+        let mut dst = Vec::new();
+        servers.serialize(&mut dst).unwrap();
+        assert_eq!(dst.len(), 4 + 2 * 38);
+
+        let mut src = Cursor::new(dst.as_slice());
+        let decoded = Vec::<ServerEntry>::deserialize(&mut src).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[1].name, "Grag");
+        assert_eq!(decoded[1].status, ServerStatus::New);
+
+        let mut dst = Vec::new();
+        ServerList { servers }.serialize(&mut dst).unwrap();
+        assert_eq!(dst[0], 2);
+        assert_eq!(dst.len(), 1 + 2 * 38);
+
+        let list = ServerList::deserialize(&mut Cursor::new(dst.as_slice())).unwrap();
+        assert_eq!(list.servers[0].name, "Teos");
+    }
+}